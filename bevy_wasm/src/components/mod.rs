@@ -1,5 +1,8 @@
 //! Components
 
-pub use wasm_mod::WasmMod;
+pub use crate::runtime::WasmInstance;
+pub use wasm_mod::{BudgetPolicy, WasmMod};
+pub use wasm_mod_status::WasmModStatus;
 
 mod wasm_mod;
+mod wasm_mod_status;
@@ -1,18 +1,111 @@
-use bevy::prelude::*;
+use std::time::Duration;
 
-use crate::wasm_asset::WasmAsset;
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{limits::ModLimits, wasm_asset::WasmAsset};
 
 /// The [`WasmMod`] component is used to spawn a new WebAssembly Mod into the world
 ///
 /// # Example
 ///
-/// ```
-/// commands.spawn(WasmMod {
-///     wasm: asset_server.load("my_mod.wasm"),
-/// });
+/// ```ignore
+/// commands.spawn(WasmMod::new(asset_server.load("my_mod.wasm")).with_fuel(1_000_000));
 /// ```
 #[derive(Component)]
 pub struct WasmMod {
     /// Handle to the underlying WebAssembly binary
     pub wasm: Handle<WasmAsset>,
+
+    /// Maximum amount of fuel the mod may consume during a single tick. `None` means unlimited.
+    ///
+    /// Most WebAssembly instructions consume one unit of fuel. Calls made with
+    /// [`WasmInstance::call`](super::WasmInstance::call) get the same budget.
+    /// Only enforced by the native runtime.
+    pub fuel: Option<u64>,
+
+    /// Maximum wall-clock time a single call into the mod may take. `None` means unlimited.
+    ///
+    /// Applies to `build_app`, every `update`, and every [`WasmInstance::call`](super::WasmInstance::call).
+    /// Unlike fuel, this also covers time spent in host calls, but is only checked about once per millisecond.
+    /// Only enforced by the native runtime.
+    pub deadline: Option<Duration>,
+
+    /// Resource limits for this mod. `None` uses the limits set on the
+    /// [`WasmPlugin`](crate::plugin::WasmPlugin).
+    pub limits: Option<ModLimits>,
+
+    /// What to do with the mod when it exceeds its fuel budget or deadline
+    pub on_budget_exceeded: BudgetPolicy,
+
+    /// Tags that give the mod access to restricted shared resources, see
+    /// [`ResourceAccess::Capability`](crate::shared_resources::ResourceAccess::Capability)
+    pub capabilities: HashSet<String>,
+}
+
+impl WasmMod {
+    /// Create a new mod from a handle to its WebAssembly binary
+    pub fn new(wasm: Handle<WasmAsset>) -> Self {
+        Self {
+            wasm,
+            fuel: None,
+            deadline: None,
+            limits: None,
+            on_budget_exceeded: BudgetPolicy::default(),
+            capabilities: HashSet::new(),
+        }
+    }
+
+    /// Limit the amount of fuel the mod may consume during a single tick
+    pub fn with_fuel(self, fuel: u64) -> Self {
+        Self {
+            fuel: Some(fuel),
+            ..self
+        }
+    }
+
+    /// Limit how long a single call into the mod may take
+    pub fn with_deadline(self, deadline: Duration) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..self
+        }
+    }
+
+    /// Limit the resources the mod may allocate, overriding the plugin's limits
+    pub fn with_limits(self, limits: ModLimits) -> Self {
+        Self {
+            limits: Some(limits),
+            ..self
+        }
+    }
+
+    /// Set what happens to the mod when it exceeds its fuel budget or deadline
+    pub fn with_budget_policy(self, policy: BudgetPolicy) -> Self {
+        Self {
+            on_budget_exceeded: policy,
+            ..self
+        }
+    }
+
+    /// Give the mod a capability, which restricted shared resources may require
+    pub fn with_capability(mut self, capability: impl Into<String>) -> Self {
+        self.capabilities.insert(capability.into());
+        self
+    }
+}
+
+/// What to do with a mod that exceeded its fuel budget or deadline
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BudgetPolicy {
+    /// Throw away whatever the mod did this frame and tick it again next frame
+    #[default]
+    Skip,
+
+    /// Stop ticking the mod by marking it as [`Failed`](super::WasmModStatus::Failed).
+    ///
+    /// Set its status back to [`Running`](super::WasmModStatus::Running) to resume the mod.
+    Suspend,
+
+    /// Despawn the mod's entity
+    Despawn,
 }
@@ -0,0 +1,24 @@
+use bevy::prelude::*;
+
+/// Where a [`WasmMod`](super::WasmMod) is in its lifecycle
+///
+/// Inserted automatically. Only [`Running`](WasmModStatus::Running) mods are ticked.
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub enum WasmModStatus {
+    /// Waiting for the WebAssembly binary to load and be instantiated
+    Loading,
+
+    /// Instantiated and ticked every frame
+    Running,
+
+    /// The mod could not be loaded, or was stopped after misbehaving. Contains the reason.
+    ///
+    /// Set the status back to [`Running`](WasmModStatus::Running) to resume a stopped mod.
+    Failed(String),
+
+    /// The mod was built for a protocol version the game doesn't support
+    Incompatible,
+
+    /// The [`WasmMod`](super::WasmMod) component was removed from the entity
+    Unloaded,
+}
@@ -0,0 +1,211 @@
+//! Errors caused by misbehaving mods
+
+use std::{fmt, time::Duration};
+
+use bevy::prelude::Entity;
+use bevy_wasm_shared::{
+    codec::{Codec, CodecError},
+    version::Version,
+};
+
+/// An error caused by a mod
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModError {
+    /// The mod consumed all of its fuel during a single tick
+    BudgetExceeded {
+        /// The mod's entity
+        entity: Entity,
+        /// The mod's fuel budget
+        fuel: u64,
+    },
+
+    /// The mod ran past its wall-clock deadline
+    DeadlineExceeded {
+        /// The mod's entity
+        entity: Entity,
+        /// The mod's deadline
+        deadline: Duration,
+    },
+
+    /// The mod was built for a protocol version the game doesn't support
+    IncompatibleVersion {
+        /// The mod's entity
+        entity: Entity,
+        /// The game's protocol version
+        host_version: Version,
+        /// The protocol version the mod was built for, `None` if it doesn't export one
+        mod_version: Option<Version>,
+    },
+
+    /// The mod was built against a `bevy_wasm_sys` that talks to the game differently
+    IncompatibleFfi {
+        /// The mod's entity
+        entity: Entity,
+        /// The game's FFI version
+        host_version: u32,
+        /// The FFI version the mod was built for, if it exports one
+        mod_version: Option<u32>,
+    },
+
+    /// The mod serializes messages and shared resources with another codec than the game
+    IncompatibleCodec {
+        /// The mod's entity
+        entity: Entity,
+        /// The game's codec
+        host_codec: Codec,
+        /// The mod's codec, if it exports one the game knows
+        mod_codec: Option<Codec>,
+    },
+
+    /// The mod's `build_app` returned without storing its app, usually because the mod
+    /// doesn't support the game's protocol version
+    AppNotBuilt {
+        /// The mod's entity
+        entity: Entity,
+    },
+}
+
+impl fmt::Display for ModError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModError::BudgetExceeded { entity, fuel } => {
+                write!(f, "Mod {:?} exceeded its budget of {} fuel", entity, fuel)
+            }
+            ModError::DeadlineExceeded { entity, deadline } => {
+                write!(
+                    f,
+                    "Mod {:?} exceeded its deadline of {:?}",
+                    entity, deadline
+                )
+            }
+            ModError::IncompatibleVersion {
+                entity,
+                host_version,
+                mod_version: Some(mod_version),
+            } => {
+                write!(
+                    f,
+                    "Mod {:?} has an incompatible protocol version! Host: {}.{}.{}, Mod: {}.{}.{}",
+                    entity,
+                    host_version.major,
+                    host_version.minor,
+                    host_version.patch,
+                    mod_version.major,
+                    mod_version.minor,
+                    mod_version.patch
+                )
+            }
+            ModError::IncompatibleVersion {
+                entity,
+                host_version,
+                mod_version: None,
+            } => {
+                write!(
+                    f,
+                    "Mod {:?} doesn't export its protocol version, the game uses {}.{}.{}",
+                    entity, host_version.major, host_version.minor, host_version.patch
+                )
+            }
+            ModError::IncompatibleFfi {
+                entity,
+                host_version,
+                mod_version: Some(mod_version),
+            } => {
+                write!(
+                    f,
+                    "Mod {:?} was built for FFI version {}, but the game uses {}",
+                    entity, mod_version, host_version
+                )
+            }
+            ModError::IncompatibleFfi {
+                entity,
+                host_version,
+                mod_version: None,
+            } => {
+                write!(
+                    f,
+                    "Mod {:?} doesn't export its FFI version {}, build it with export_protocol_version!",
+                    entity, host_version
+                )
+            }
+            ModError::IncompatibleCodec {
+                entity,
+                host_codec,
+                mod_codec: Some(mod_codec),
+            } => {
+                write!(
+                    f,
+                    "Mod {:?} serializes with {:?}, but the game uses {:?}",
+                    entity, mod_codec, host_codec
+                )
+            }
+            ModError::IncompatibleCodec {
+                entity,
+                host_codec,
+                mod_codec: None,
+            } => {
+                write!(
+                    f,
+                    "Mod {:?} doesn't export a codec the game knows, the game uses {:?}",
+                    entity, host_codec
+                )
+            }
+            ModError::AppNotBuilt { entity } => {
+                write!(
+                    f,
+                    "Mod {:?} didn't build its app, it probably rejected the game's protocol version",
+                    entity
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModError {}
+
+/// Why calling a mod's export with [`WasmInstance::call`](crate::components::WasmInstance::call) failed
+#[derive(Debug)]
+pub enum CallError {
+    /// The mod hasn't finished loading
+    NotLoaded,
+
+    /// The mod has no export with that name, or it wasn't made with `#[bevy_wasm_sys::export]`
+    MissingExport(String),
+
+    /// The arguments or the result could not be serialized
+    Codec(CodecError),
+
+    /// The mod didn't return a result, usually because it couldn't read the arguments
+    NoResult,
+
+    /// The mod trapped, or exceeded its budget or deadline with a [`ModError`]
+    Failed(anyhow::Error),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::NotLoaded => write!(f, "The mod hasn't finished loading"),
+            CallError::MissingExport(name) => write!(f, "The mod doesn't export {}", name),
+            CallError::Codec(err) => write!(f, "{}", err),
+            CallError::NoResult => write!(f, "The mod didn't return a result"),
+            CallError::Failed(err) => write!(f, "{:#}", err),
+        }
+    }
+}
+
+impl std::error::Error for CallError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CallError::Codec(err) => Some(err),
+            CallError::Failed(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<CodecError> for CallError {
+    fn from(err: CodecError) -> Self {
+        CallError::Codec(err)
+    }
+}
@@ -0,0 +1,63 @@
+//! Events sent to and from mods, and as mods are loaded and unloaded
+
+use bevy::prelude::Entity;
+
+/// Send a message to a single mod instead of every mod
+///
+/// Sending an `In` event directly broadcasts it to all mods.
+/// Targeted messages are delivered after the broadcast messages sent in the same frame.
+#[derive(Clone, Debug)]
+pub struct ToMod<In> {
+    /// The mod's entity
+    pub target: Entity,
+
+    /// The message to send
+    pub message: In,
+}
+
+/// A message sent by a mod, along with the mod that sent it
+///
+/// Only sent after [`WasmPlugin::tag_messages`](crate::plugin::WasmPlugin::tag_messages),
+/// in place of the plain `Out` event.
+#[derive(Clone, Debug)]
+pub struct FromMod<Out> {
+    /// The mod's entity
+    pub source: Entity,
+
+    /// The message the mod sent
+    pub message: Out,
+}
+
+/// A mod was instantiated and is now [`Running`](crate::components::WasmModStatus::Running)
+#[derive(Clone, Debug)]
+pub struct ModLoaded {
+    /// The mod's entity
+    pub entity: Entity,
+}
+
+/// A mod's WebAssembly binary changed on disk and the mod was instantiated again
+///
+/// Sent instead of [`ModLoaded`] when a reloaded mod is
+/// [`Running`](crate::components::WasmModStatus::Running) again.
+#[derive(Clone, Debug)]
+pub struct ModReloaded {
+    /// The mod's entity
+    pub entity: Entity,
+}
+
+/// A mod failed to load, or was stopped after misbehaving
+#[derive(Clone, Debug)]
+pub struct ModFailed {
+    /// The mod's entity
+    pub entity: Entity,
+
+    /// Why the mod failed
+    pub reason: String,
+}
+
+/// A mod's [`WasmMod`](crate::components::WasmMod) component was removed, or its entity was despawned
+#[derive(Clone, Debug)]
+pub struct ModUnloaded {
+    /// The mod's entity
+    pub entity: Entity,
+}
@@ -11,9 +11,14 @@ use bevy::{prelude::Resource, reflect::TypeUuid};
 use serde::{de::DeserializeOwned, Serialize};
 
 pub mod components;
+pub mod error;
+pub mod events;
+pub mod limits;
 mod mod_state;
 pub mod plugin;
+pub mod rpc;
 mod runtime;
+pub mod shared_resources;
 mod systems;
 mod wasm_asset;
 
@@ -21,7 +26,8 @@ mod wasm_asset;
 ///
 /// Must be [`Clone`], [`Send`], and [`Sync`], and must be (de)serializable with serde.
 ///
-/// `bevy_wasm` uses `bincode` for serialization, so it's relatively fast.
+/// `bevy_wasm` uses `bincode` for serialization by default, so it's relatively fast.
+/// Pick another [`Codec`](bevy_wasm_shared::codec::Codec) with [`WasmPlugin::with_codec`](plugin::WasmPlugin::with_codec).
 pub trait Message: Send + Sync + Serialize + DeserializeOwned + Clone + 'static {}
 
 impl<T> Message for T where T: Send + Sync + Serialize + DeserializeOwned + Clone + 'static {}
@@ -35,6 +41,15 @@ impl<T> SharedResource for T where T: Resource + Serialize + DeserializeOwned +
 
 /// Convinience exports
 pub mod prelude {
-    pub use crate::{components::*, plugin::WasmPlugin, Message};
+    pub use crate::{
+        components::*,
+        error::{CallError, ModError},
+        events::{FromMod, ModFailed, ModLoaded, ModReloaded, ModUnloaded, ToMod},
+        limits::ModLimits,
+        plugin::WasmPlugin,
+        rpc::{ModRequest, Responder},
+        shared_resources::{ConflictPolicy, ResourceAccess},
+        Message,
+    };
     pub use bevy_wasm_shared::prelude::*;
 }
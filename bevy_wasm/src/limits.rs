@@ -0,0 +1,20 @@
+//! Limits on the resources a mod may allocate
+
+/// Limits on the resources a mod may allocate. `None` means unlimited.
+///
+/// Set them for every mod with [`WasmPlugin::with_limits`](crate::plugin::WasmPlugin::with_limits),
+/// or for a single mod with [`WasmMod::with_limits`](crate::components::WasmMod::with_limits).
+///
+/// Growing past a limit fails inside the mod (`memory.grow` returns `-1`) instead of
+/// taking down the game. Only enforced by the native runtime.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ModLimits {
+    /// Maximum size of each linear memory, in 64 KiB pages
+    pub max_memory_pages: Option<u64>,
+
+    /// Maximum number of elements in each table
+    pub max_table_elements: Option<u32>,
+
+    /// Maximum number of instances, counting the mod itself
+    pub max_instances: Option<usize>,
+}
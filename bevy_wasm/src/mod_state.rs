@@ -1,9 +1,29 @@
 use std::{collections::VecDeque, sync::Arc};
 
-use bevy::utils::{HashMap, Instant, Uuid};
+use bevy::{
+    prelude::Entity,
+    utils::{HashMap, HashSet, Instant, Uuid},
+};
+use bevy_wasm_shared::codec::Codec;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::limits::ModLimits;
 
 /// Internal mod state
 pub struct ModState {
+    /// The mod's entity
+    pub entity: Entity,
+
+    /// Unique to this instance of the mod, unlike its entity
+    pub instance_id: u64,
+
+    /// The codec messages are serialized with
+    pub codec: Codec,
+
+    /// Limits on the resources the mod may allocate
+    #[cfg(not(target_arch = "wasm32"))]
+    pub limits: ModLimits,
+
     /// Time when the mod was loaded
     pub startup_time: Instant,
 
@@ -16,6 +36,69 @@ pub struct ModState {
     /// Events that have been sent to the host
     pub events_out: Vec<Box<[u8]>>,
 
-    /// Resources that have changed since the last update
+    /// Events sent to the mod on additional channels, by channel id
+    pub channels_in: HashMap<Uuid, VecDeque<Arc<[u8]>>>,
+
+    /// Events sent to the host on additional channels, by channel id
+    pub channels_out: HashMap<Uuid, Vec<Box<[u8]>>>,
+
+    /// Resources that have changed since the last update, as serialized `ResourceUpdate`s
     pub shared_resource_values: HashMap<Uuid, Arc<[u8]>>,
+
+    /// Version of the last value of each shared resource the mod read
+    pub resource_versions: HashMap<Uuid, u64>,
+
+    /// Shared resources the mod lost track of, and needs the whole value of
+    pub resource_resyncs: HashSet<Uuid>,
+
+    /// Shared resources the mod may not read or write
+    pub denied_resources: HashSet<Uuid>,
+
+    /// Shared resources the game lets mods write to
+    pub writable_resources: HashSet<Uuid>,
+
+    /// Values the mod wrote to shared resources since they were last applied, by resource id
+    pub resource_writes: HashMap<Uuid, Box<[u8]>>,
+
+    /// The serialized result of the last host function the mod called, until the mod takes it
+    pub host_function_result: Option<Box<[u8]>>,
+
+    /// The serialized arguments of the export the host is calling, until the mod takes them
+    pub call_args: Option<Box<[u8]>>,
+
+    /// The serialized result of the export the host is calling
+    pub call_result: Option<Box<[u8]>>,
+
+    /// State saved by the mod with `store_state`, or handed to it after a hot reload
+    pub saved_state: Option<Box<[u8]>>,
+}
+
+#[cfg(test)]
+impl ModState {
+    /// State of a mod that hasn't sent or received anything yet
+    pub(crate) fn new_for_test(entity: Entity) -> Self {
+        Self {
+            entity,
+            instance_id: 0,
+            codec: Codec::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            limits: ModLimits::default(),
+            startup_time: Instant::now(),
+            app_ptr: 0,
+            events_in: VecDeque::new(),
+            events_out: Vec::new(),
+            channels_in: HashMap::new(),
+            channels_out: HashMap::new(),
+            shared_resource_values: HashMap::new(),
+            resource_versions: HashMap::new(),
+            resource_resyncs: HashSet::new(),
+            denied_resources: HashSet::new(),
+            writable_resources: HashSet::new(),
+            resource_writes: HashMap::new(),
+            host_function_result: None,
+            call_args: None,
+            call_result: None,
+            saved_state: None,
+        }
+    }
 }
@@ -1,11 +1,22 @@
 //! Add this plugin to your Bevy app to enable WASM-based modding
 
-use bevy::prelude::*;
-use bevy_wasm_shared::prelude::*;
+use std::{path::PathBuf, sync::Arc};
+
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{HashMap, Uuid},
+};
+use bevy_wasm_shared::{prelude::*, version::default_version_checker};
 use colored::*;
+use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{
-    runtime::WasmRuntime,
+    events::{FromMod, ModFailed, ModLoaded, ModReloaded, ModUnloaded, ToMod},
+    limits::ModLimits,
+    rpc::{ModRequest, Responses},
+    runtime::{HostFunction, VersionChecker, WasmRuntime},
+    shared_resources::{ConflictPolicy, DeltaEncoded, ResourceAccess, WritableResource},
     systems::{self, load_instances},
     wasm_asset::{WasmAsset, WasmAssetLoader},
     Message, SharedResource,
@@ -15,13 +26,89 @@ trait AddSystemToApp: Send + Sync + 'static {
     fn add_system_to_app(&self, app: &mut App);
 }
 
-struct ResourceUpdater<R: SharedResource> {
+struct ResourceUpdater<In, Out, R> {
+    _r: std::marker::PhantomData<(In, Out, R)>,
+}
+
+impl<In: Message, Out: Message, R: SharedResource> AddSystemToApp for ResourceUpdater<In, Out, R> {
+    fn add_system_to_app(&self, app: &mut App) {
+        // Before new instances are created, so they start with the latest value
+        app.add_system(
+            systems::update_shared_resource::<R>
+                .before(load_instances)
+                .before(systems::tick_mods::<In, Out>),
+        );
+    }
+}
+
+struct ResourceWriteBack<In, Out, R> {
+    policy: ConflictPolicy,
+    _r: std::marker::PhantomData<(In, Out, R)>,
+}
+
+impl<In: Message, Out: Message, R: SharedResource> AddSystemToApp
+    for ResourceWriteBack<In, Out, R>
+{
+    fn add_system_to_app(&self, app: &mut App) {
+        app.world
+            .resource_mut::<WasmRuntime>()
+            .allow_resource_writes::<R>();
+        app.insert_resource(WritableResource::<R>::new(self.policy))
+            .add_system(systems::apply_resource_writes::<R>.after(systems::tick_mods::<In, Out>));
+    }
+}
+
+struct ResourceDeltas<R> {
     _r: std::marker::PhantomData<R>,
 }
 
-impl<R: SharedResource> AddSystemToApp for ResourceUpdater<R> {
+impl<R: SharedResource> AddSystemToApp for ResourceDeltas<R> {
+    fn add_system_to_app(&self, app: &mut App) {
+        app.init_resource::<DeltaEncoded<R>>();
+    }
+}
+
+struct Channel<In, Out, ChannelIn, ChannelOut> {
+    _messages: std::marker::PhantomData<(In, Out, ChannelIn, ChannelOut)>,
+}
+
+impl<In, Out, ChannelIn, ChannelOut> AddSystemToApp for Channel<In, Out, ChannelIn, ChannelOut>
+where
+    In: Message,
+    Out: Message,
+    ChannelIn: Message + TypeUuid,
+    ChannelOut: Message + TypeUuid,
+{
+    fn add_system_to_app(&self, app: &mut App) {
+        app.add_event::<ChannelIn>()
+            .add_event::<ToMod<ChannelIn>>()
+            .add_event::<ChannelOut>()
+            .add_event::<FromMod<ChannelOut>>()
+            .add_system(
+                systems::send_channel_messages::<ChannelIn>.before(systems::tick_mods::<In, Out>),
+            )
+            .add_system(
+                systems::receive_channel_messages::<ChannelOut>
+                    .after(systems::tick_mods::<In, Out>),
+            );
+    }
+}
+
+struct Rpc<In, Out, Req> {
+    _messages: std::marker::PhantomData<(In, Out, Req)>,
+}
+
+impl<In, Out, Req> AddSystemToApp for Rpc<In, Out, Req>
+where
+    In: Message,
+    Out: Message,
+    Req: Request + TypeUuid,
+{
     fn add_system_to_app(&self, app: &mut App) {
-        app.add_system(systems::update_shared_resource::<R>);
+        app.init_resource::<Responses<Req>>()
+            .add_event::<ModRequest<Req>>()
+            .add_system(systems::send_mod_responses::<Req>.before(systems::tick_mods::<In, Out>))
+            .add_system(systems::receive_mod_requests::<Req>.after(systems::tick_mods::<In, Out>));
     }
 }
 
@@ -35,7 +122,17 @@ where
     Out: Message,
 {
     protocol_version: Version,
+    limits: ModLimits,
+    version_checker: VersionChecker,
+    allow_unversioned_mods: bool,
+    module_cache: Option<PathBuf>,
+    parallel_ticking: bool,
+    tag_messages: bool,
+    codec: Codec,
     shared_resources: Vec<Box<dyn AddSystemToApp>>,
+    channels: Vec<Box<dyn AddSystemToApp>>,
+    host_functions: HashMap<String, HostFunction>,
+    resource_access: HashMap<Uuid, ResourceAccess>,
     _in: std::marker::PhantomData<In>,
     _out: std::marker::PhantomData<Out>,
 }
@@ -59,35 +156,222 @@ impl<In: Message, Out: Message> WasmPlugin<In, Out> {
         );
         WasmPlugin {
             protocol_version,
+            limits: ModLimits::default(),
+            version_checker: Arc::new(default_version_checker),
+            allow_unversioned_mods: false,
+            module_cache: None,
+            parallel_ticking: false,
+            tag_messages: false,
+            codec: Codec::default(),
             shared_resources: Vec::new(),
+            channels: Vec::new(),
+            host_functions: HashMap::new(),
+            resource_access: HashMap::new(),
             _in: std::marker::PhantomData,
             _out: std::marker::PhantomData,
         }
     }
 
+    /// Limit the resources every mod may allocate
+    ///
+    /// Individual mods can override this with [`WasmMod::with_limits`].
+    pub fn with_limits(mut self, limits: ModLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set a custom protocol version checker to reject mods built for an incompatible protocol
+    ///
+    /// The checker is called with the game's version first and the mod's version second.
+    /// The default version checker only ensures the major versions match.
+    ///
+    /// Mods tell the game their version with `bevy_wasm_sys::export_protocol_version!`.
+    /// Rejected mods get the [`Incompatible`](crate::components::WasmModStatus::Incompatible) status.
+    pub fn with_version_checker<F>(mut self, checker: F) -> Self
+    where
+        F: Fn(Version, Version) -> bool + Send + Sync + 'static,
+    {
+        self.version_checker = Arc::new(checker);
+        self
+    }
+
+    /// Load mods that don't export their protocol version, assuming they are compatible
+    ///
+    /// By default they get the [`Incompatible`](crate::components::WasmModStatus::Incompatible)
+    /// status, like mods built for another version.
+    pub fn allow_unversioned_mods(mut self) -> Self {
+        self.allow_unversioned_mods = true;
+        self
+    }
+
+    /// Cache compiled mods in `dir`, so they don't have to be compiled again on the next launch
+    ///
+    /// Anything in this directory is loaded as native code, so only point it at a directory
+    /// your game controls. Not used in the browser.
+    pub fn with_module_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.module_cache = Some(dir.into());
+        self
+    }
+
+    /// Tick mods concurrently on the compute task pool instead of one after another
+    ///
+    /// Events sent by mods are still written in a deterministic order, sorted by the mod's entity.
+    pub fn with_parallel_ticking(mut self) -> Self {
+        self.parallel_ticking = true;
+        self
+    }
+
+    /// Send messages from mods as [`FromMod`] events, tagged with the mod that sent them
+    ///
+    /// Each message is sent once, so they no longer arrive as plain `Out` events.
+    /// Applies to additional channels too.
+    pub fn tag_messages(mut self) -> Self {
+        self.tag_messages = true;
+        self
+    }
+
+    /// Serialize messages and shared resources with `codec` instead of bincode
+    ///
+    /// Mods must use the same codec, set with `FFIPlugin::with_codec`.
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
     /// Register a resource to be shared with mods. THIS SHOULD COME FROM YOUR PROTOCOL CRATE
     pub fn share_resource<T: SharedResource>(mut self) -> Self {
-        self.shared_resources.push(Box::new(ResourceUpdater::<T> {
+        self.shared_resources
+            .push(Box::new(ResourceUpdater::<In, Out, T> {
+                _r: std::marker::PhantomData,
+            }));
+        self
+    }
+
+    /// Register a resource to be shared with mods, and let them change it with `ExternResMut`
+    ///
+    /// Changes mods make are applied after they are ticked, following `policy`.
+    /// THIS SHOULD COME FROM YOUR PROTOCOL CRATE
+    pub fn share_resource_mut<T: SharedResource>(mut self, policy: ConflictPolicy) -> Self {
+        self = self.share_resource::<T>();
+        self.shared_resources
+            .push(Box::new(ResourceWriteBack::<In, Out, T> {
+                policy,
+                _r: std::marker::PhantomData,
+            }));
+        self
+    }
+
+    /// Send changes to the shared resource `T` as patches against the version each mod last read,
+    /// instead of the whole value
+    ///
+    /// Worth it for large resources where little changes at a time, such as grids and tables.
+    /// Mods that miss a version get the whole value again. Share `T` with [`Self::share_resource`]
+    /// or [`Self::share_resource_mut`] as well.
+    pub fn with_resource_deltas<T: SharedResource>(mut self) -> Self {
+        self.shared_resources.push(Box::new(ResourceDeltas::<T> {
             _r: std::marker::PhantomData,
         }));
         self
     }
+
+    /// Only let the mods `access` allows read the shared resource `T`. Every mod may read it otherwise.
+    ///
+    /// Share `T` with [`Self::share_resource`] or [`Self::share_resource_mut`] as well.
+    pub fn with_resource_access<T: SharedResource>(mut self, access: ResourceAccess) -> Self {
+        self.resource_access.insert(T::TYPE_UUID, access);
+        self
+    }
+
+    /// Add a channel with its own message types, next to the plugin's `In` and `Out` messages
+    ///
+    /// Messages are sent and received like the plugin's own, including with [`ToMod`] and [`FromMod`].
+    /// Each direction is identified by its message type's [`TypeUuid`], so mods must register the
+    /// same channel with `FFIPlugin::add_channel`. THESE SHOULD COME FROM YOUR PROTOCOL CRATE
+    pub fn add_channel<ChannelIn, ChannelOut>(mut self) -> Self
+    where
+        ChannelIn: Message + TypeUuid,
+        ChannelOut: Message + TypeUuid,
+    {
+        self.channels
+            .push(Box::new(Channel::<In, Out, ChannelIn, ChannelOut> {
+                _messages: std::marker::PhantomData,
+            }));
+        self
+    }
+
+    /// Let mods send requests of type `Req`, and answer them with a `Req::Response`
+    ///
+    /// Requests arrive as [`ModRequest`] events. Mods must register the same request with
+    /// `FFIPlugin::add_rpc`. THIS SHOULD COME FROM YOUR PROTOCOL CRATE
+    pub fn add_rpc<Req: Request + TypeUuid>(mut self) -> Self {
+        self.channels.push(Box::new(Rpc::<In, Out, Req> {
+            _messages: std::marker::PhantomData,
+        }));
+        self
+    }
+
+    /// Expose a function that mods can call by `name` while they are ticked, and get its result right away
+    ///
+    /// Arguments and results are serialized with the plugin's codec. Mods declare the function with
+    /// `bevy_wasm_sys::host_function!`, using the same name and types. The function runs on the
+    /// thread ticking the mod and has no access to the world, so keep it quick and self-contained.
+    pub fn add_host_function<Args, Ret, F>(mut self, name: impl Into<String>, function: F) -> Self
+    where
+        Args: DeserializeOwned,
+        Ret: Serialize,
+        F: Fn(Args) -> Ret + Send + Sync + 'static,
+    {
+        let function: HostFunction = Arc::new(move |codec: Codec, args: &[u8]| {
+            let args = codec.decode::<Args>(args)?;
+            codec.encode(&function(args))
+        });
+        self.host_functions.insert(name.into(), function);
+        self
+    }
 }
 
 impl<In: Message, Out: Message> Plugin for WasmPlugin<In, Out> {
     fn build(&self, app: &mut App) {
-        let wasm_resource = WasmRuntime::new(self.protocol_version);
+        let mut wasm_resource = WasmRuntime::new(
+            self.protocol_version,
+            self.limits,
+            self.version_checker.clone(),
+            self.module_cache.clone(),
+            self.codec,
+            Arc::new(self.host_functions.clone()),
+            self.resource_access.clone(),
+        );
+        if self.allow_unversioned_mods {
+            wasm_resource.allow_unversioned_mods();
+        }
 
         app.insert_resource(wasm_resource)
+            .insert_resource(systems::TickSettings {
+                parallel: self.parallel_ticking,
+            })
+            .insert_resource(systems::ProtocolCodec(self.codec))
+            .insert_resource(systems::TagMessages(self.tag_messages))
             .add_asset::<WasmAsset>()
             .init_asset_loader::<WasmAssetLoader>()
             .add_event::<In>()
+            .add_event::<ToMod<In>>()
             .add_event::<Out>()
+            .add_event::<FromMod<Out>>()
+            .add_event::<ModLoaded>()
+            .add_event::<ModReloaded>()
+            .add_event::<ModFailed>()
+            .add_event::<ModUnloaded>()
+            .add_system(systems::reload_instances.before(load_instances))
             .add_system(load_instances)
+            .add_system(systems::unload_instances)
             .add_system(systems::tick_mods::<In, Out>);
 
         for system in self.shared_resources.iter() {
             system.add_system_to_app(app);
         }
+
+        for channel in self.channels.iter() {
+            channel.add_system_to_app(app);
+        }
     }
 }
@@ -0,0 +1,115 @@
+//! Answer requests sent by mods
+
+use std::{
+    fmt::{self, Debug},
+    sync::{Arc, Mutex},
+};
+
+use bevy::prelude::{Entity, Resource};
+use bevy_wasm_shared::rpc::Request;
+
+/// A request sent by a mod. Answer it with [`ModRequest::respond`].
+///
+/// Register requests with [`WasmPlugin::add_rpc`](crate::plugin::WasmPlugin::add_rpc).
+#[derive(Clone, Debug)]
+pub struct ModRequest<Req: Request> {
+    /// The mod's entity
+    pub source: Entity,
+
+    /// The request the mod sent
+    pub request: Req,
+
+    /// Answers this request. Keep a clone to answer it in a later frame.
+    pub responder: Responder<Req::Response>,
+}
+
+impl<Req: Request> ModRequest<Req> {
+    /// Answer the request
+    pub fn respond(&self, response: Req::Response) {
+        self.responder.respond(response);
+    }
+}
+
+/// Answers a single [`ModRequest`]
+///
+/// The response is delivered on the mod's next tick. Only the first response counts,
+/// and the mod gives up on requests that aren't answered before its timeout.
+pub struct Responder<Res> {
+    target: Entity,
+    instance: u64,
+    id: u64,
+    queue: ResponseQueue<Res>,
+}
+
+impl<Res> Responder<Res> {
+    /// Answer the request
+    pub fn respond(&self, response: Res) {
+        self.queue.lock().unwrap().push(QueuedResponse {
+            target: self.target,
+            instance: self.instance,
+            id: self.id,
+            response,
+        });
+    }
+}
+
+impl<Res> Clone for Responder<Res> {
+    fn clone(&self) -> Self {
+        Self {
+            target: self.target,
+            instance: self.instance,
+            id: self.id,
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<Res> Debug for Responder<Res> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Responder")
+            .field("target", &self.target)
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+/// A response waiting to be sent to the mod
+pub(crate) struct QueuedResponse<Res> {
+    pub target: Entity,
+    /// The instance that made the request. A reloaded mod doesn't get its previous instance's responses.
+    pub instance: u64,
+    /// The id the mod gave its call
+    pub id: u64,
+    pub response: Res,
+}
+
+type ResponseQueue<Res> = Arc<Mutex<Vec<QueuedResponse<Res>>>>;
+
+/// Where the [`Responder`]s of a request type queue their responses
+#[derive(Resource)]
+pub(crate) struct Responses<Req: Request> {
+    queue: ResponseQueue<Req::Response>,
+}
+
+impl<Req: Request> Responses<Req> {
+    pub fn responder(&self, target: Entity, instance: u64, id: u64) -> Responder<Req::Response> {
+        Responder {
+            target,
+            instance,
+            id,
+            queue: self.queue.clone(),
+        }
+    }
+
+    pub fn take(&self) -> Vec<QueuedResponse<Req::Response>> {
+        std::mem::take(&mut *self.queue.lock().unwrap())
+    }
+}
+
+impl<Req: Request> Default for Responses<Req> {
+    fn default() -> Self {
+        Self {
+            queue: Arc::default(),
+        }
+    }
+}
@@ -1,3 +1,22 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use bevy::{
+    prelude::{warn, Entity},
+    utils::{HashMap, HashSet, Uuid},
+};
+use bevy_wasm_shared::{
+    codec::{Codec, CodecError},
+    resource_update::ResourceUpdate,
+    version::{Version, FFI_VERSION},
+};
+
+use crate::{
+    components::WasmMod, error::ModError, mod_state::ModState, shared_resources::ResourceAccess,
+};
+
 #[cfg(target_arch = "wasm32")]
 pub use web::{WasmInstance, WasmRuntime};
 
@@ -9,3 +28,287 @@ pub mod web;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub mod native;
+
+/// Decides whether a mod built for the second version can run in a game with the first
+pub type VersionChecker = Arc<dyn Fn(Version, Version) -> bool + Send + Sync + 'static>;
+
+/// A function added with [`WasmPlugin::add_host_function`](crate::plugin::WasmPlugin::add_host_function),
+/// taking and returning serialized values
+pub type HostFunction =
+    Arc<dyn Fn(Codec, &[u8]) -> Result<Vec<u8>, CodecError> + Send + Sync + 'static>;
+
+/// Host functions by name
+pub type HostFunctions = Arc<HashMap<String, HostFunction>>;
+
+/// Check the version exported by a mod's `get_mod_ffi_version` against the game's
+fn check_ffi_version(entity: Entity, mod_version: Option<u32>) -> Result<(), ModError> {
+    if mod_version != Some(FFI_VERSION) {
+        return Err(ModError::IncompatibleFfi {
+            entity,
+            host_version: FFI_VERSION,
+            mod_version,
+        });
+    }
+
+    Ok(())
+}
+
+/// Check the version exported by a mod's `get_mod_protocol_version` against the game's
+///
+/// Mods that don't export their version are rejected, unless the game allows unversioned mods.
+fn check_protocol_version(
+    version_checker: &VersionChecker,
+    allow_unversioned_mods: bool,
+    entity: Entity,
+    host_version: Version,
+    mod_version: Option<Version>,
+) -> Result<(), ModError> {
+    let Some(mod_version) = mod_version else {
+        if !allow_unversioned_mods {
+            return Err(ModError::IncompatibleVersion {
+                entity,
+                host_version,
+                mod_version: None,
+            });
+        }
+        warn!(
+            "Mod {:?} doesn't export its protocol version, assuming it is compatible",
+            entity
+        );
+        return Ok(());
+    };
+
+    if !version_checker(host_version, mod_version) {
+        return Err(ModError::IncompatibleVersion {
+            entity,
+            host_version,
+            mod_version: Some(mod_version),
+        });
+    }
+
+    Ok(())
+}
+
+/// Check the codec exported by a mod's `get_mod_codec` against the game's, once the mod set it
+/// while building its app
+fn check_codec(entity: Entity, host_codec: Codec, mod_codec: Option<u32>) -> Result<(), ModError> {
+    let mod_codec = mod_codec
+        .and_then(|codec| u8::try_from(codec).ok())
+        .and_then(Codec::from_u8);
+    if mod_codec != Some(host_codec) {
+        return Err(ModError::IncompatibleCodec {
+            entity,
+            host_codec,
+            mod_codec,
+        });
+    }
+
+    Ok(())
+}
+
+/// A new id for an instance, never handed out before. Tells a reloaded mod apart from its previous instance.
+fn next_instance_id() -> u64 {
+    static NEXT_INSTANCE_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_INSTANCE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Keep a value the mod wrote to a shared resource until the game applies it after the next tick,
+/// if the mod may write to it. Only resources shared with `share_resource_mut` are writable.
+fn store_resource_write(mod_state: &mut ModState, uuid: Uuid, value: Box<[u8]>) {
+    if mod_state.denied_resources.contains(&uuid) {
+        warn!(
+            "Mod {:?} wrote to resource {}, which it may not access",
+            mod_state.entity, uuid
+        );
+        return;
+    }
+    if !mod_state.writable_resources.contains(&uuid) {
+        warn!(
+            "Mod {:?} wrote to resource {}, which the game doesn't let mods write to",
+            mod_state.entity, uuid
+        );
+        return;
+    }
+    mod_state.resource_writes.insert(uuid, value);
+}
+
+/// Mark the value of a shared resource as read by the mod, which makes its version the one
+/// the next patches apply to
+fn mark_resource_read(mod_state: &mut ModState, uuid: Uuid) {
+    let Some(bytes) = mod_state.shared_resource_values.remove(&uuid) else {
+        return;
+    };
+    match ResourceUpdate::decode(&bytes) {
+        Some(update) => {
+            mod_state.resource_versions.insert(uuid, update.version());
+        }
+        None => {
+            mod_state.resource_versions.remove(&uuid);
+        }
+    }
+}
+
+/// Forget the version of a shared resource the mod has, so it gets the whole value next time
+fn request_resource_resync(mod_state: &mut ModState, uuid: Uuid) {
+    mod_state.resource_versions.remove(&uuid);
+    mod_state.resource_resyncs.insert(uuid);
+}
+
+/// The shared resources a mod may not read, following the rules set with
+/// [`WasmPlugin::with_resource_access`](crate::plugin::WasmPlugin::with_resource_access)
+fn denied_resources(
+    resource_access: &HashMap<Uuid, ResourceAccess>,
+    entity: Entity,
+    wasm_mod: &WasmMod,
+) -> HashSet<Uuid> {
+    resource_access
+        .iter()
+        .filter(|(_, access)| !access.allows(entity, wasm_mod))
+        .map(|(uuid, _)| *uuid)
+        .collect()
+}
+
+/// Stop sending a shared resource to the mod, and make it whole again once the mod may read it
+fn deny_resource(mod_state: &mut ModState, uuid: Uuid) {
+    mod_state.shared_resource_values.remove(&uuid);
+    mod_state.resource_versions.remove(&uuid);
+    mod_state.denied_resources.insert(uuid);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Handle;
+    use bevy_wasm_shared::version::default_version_checker;
+
+    use super::*;
+
+    fn version(major: u16, minor: u16) -> Version {
+        Version {
+            name_hash: 1,
+            major,
+            minor,
+            patch: 0,
+        }
+    }
+
+    #[test]
+    fn protocol_versions_are_checked() {
+        let version_checker: VersionChecker = Arc::new(default_version_checker);
+        let entity = Entity::from_raw(0);
+
+        assert_eq!(
+            check_protocol_version(
+                &version_checker,
+                false,
+                entity,
+                version(1, 0),
+                Some(version(1, 2))
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check_protocol_version(
+                &version_checker,
+                false,
+                entity,
+                version(1, 0),
+                Some(version(2, 0))
+            ),
+            Err(ModError::IncompatibleVersion {
+                entity,
+                host_version: version(1, 0),
+                mod_version: Some(version(2, 0)),
+            })
+        );
+    }
+
+    #[test]
+    fn unversioned_mods_are_rejected_unless_allowed() {
+        let version_checker: VersionChecker = Arc::new(default_version_checker);
+        let entity = Entity::from_raw(0);
+
+        assert_eq!(
+            check_protocol_version(&version_checker, false, entity, version(1, 0), None),
+            Err(ModError::IncompatibleVersion {
+                entity,
+                host_version: version(1, 0),
+                mod_version: None,
+            })
+        );
+        assert_eq!(
+            check_protocol_version(&version_checker, true, entity, version(1, 0), None),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn mods_must_use_the_games_codec() {
+        let entity = Entity::from_raw(0);
+        let incompatible = |mod_codec| {
+            Err(ModError::IncompatibleCodec {
+                entity,
+                host_codec: Codec::Bincode,
+                mod_codec,
+            })
+        };
+
+        assert_eq!(check_codec(entity, Codec::Bincode, Some(0)), Ok(()));
+        assert_eq!(
+            check_codec(entity, Codec::Bincode, Some(2)),
+            incompatible(Codec::from_u8(2))
+        );
+        assert_eq!(
+            check_codec(entity, Codec::Bincode, Some(256)),
+            incompatible(None)
+        );
+        assert_eq!(
+            check_codec(entity, Codec::Bincode, None),
+            incompatible(None)
+        );
+    }
+
+    #[test]
+    fn resources_follow_their_access_rules() {
+        let everyone = Uuid::from_u128(1);
+        let admins = Uuid::from_u128(2);
+        let first_mod = Uuid::from_u128(3);
+        let resource_access = HashMap::from_iter([
+            (everyone, ResourceAccess::Everyone),
+            (admins, ResourceAccess::Capability("admin".into())),
+            (
+                first_mod,
+                ResourceAccess::custom(|entity, _| entity.index() == 0),
+            ),
+        ]);
+        let wasm_mod = WasmMod::new(Handle::default());
+        let admin_mod = WasmMod::new(Handle::default()).with_capability("admin");
+
+        assert_eq!(
+            denied_resources(&resource_access, Entity::from_raw(0), &wasm_mod),
+            HashSet::from_iter([admins])
+        );
+        assert_eq!(
+            denied_resources(&resource_access, Entity::from_raw(1), &admin_mod),
+            HashSet::from_iter([first_mod])
+        );
+    }
+
+    #[test]
+    fn denied_resources_are_taken_away() {
+        let uuid = Uuid::from_u128(1);
+        let mut mod_state = ModState::new_for_test(Entity::from_raw(0));
+        mod_state.writable_resources.insert(uuid);
+        mod_state
+            .shared_resource_values
+            .insert(uuid, Arc::from(&b"value"[..]));
+        mod_state.resource_versions.insert(uuid, 1);
+
+        deny_resource(&mut mod_state, uuid);
+        assert!(mod_state.shared_resource_values.is_empty());
+        assert!(mod_state.resource_versions.is_empty());
+        assert!(mod_state.denied_resources.contains(&uuid));
+
+        store_resource_write(&mut mod_state, uuid, Box::from(&b"written"[..]));
+        assert!(mod_state.resource_writes.is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use wasmtime::Engine;
+
+/// How often the engine's epoch is incremented
+const EPOCH_TICK: Duration = Duration::from_millis(1);
+
+/// Epoch deadline used for mods without a deadline. Far enough away to never be reached,
+/// but small enough that adding it to the current epoch can't overflow.
+pub const NO_DEADLINE: u64 = u64::MAX / 2;
+
+/// Increments the epoch of an [`Engine`] from a background thread until dropped
+pub struct EpochTicker {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EpochTicker {
+    pub fn new(engine: Engine) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::Builder::new()
+            .name("bevy_wasm epoch ticker".into())
+            .spawn({
+                let stop = stop.clone();
+                move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        thread::sleep(EPOCH_TICK);
+                        engine.increment_epoch();
+                    }
+                }
+            })
+            .expect("Failed to spawn the epoch ticker thread");
+
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Number of epoch ticks that are guaranteed to take at least `duration`
+    pub fn ticks(duration: Duration) -> u64 {
+        (duration.as_nanos() / EPOCH_TICK.as_nanos()) as u64 + 1
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadlines_round_up_to_whole_ticks() {
+        assert_eq!(EpochTicker::ticks(Duration::ZERO), 1);
+        assert_eq!(EpochTicker::ticks(Duration::from_micros(500)), 1);
+        assert_eq!(EpochTicker::ticks(Duration::from_millis(1)), 2);
+        assert_eq!(EpochTicker::ticks(Duration::from_millis(16)), 17);
+    }
+}
@@ -0,0 +1,121 @@
+use bevy::prelude::warn;
+use wasmtime::{ResourceLimiter, DEFAULT_INSTANCE_LIMIT};
+
+use crate::mod_state::ModState;
+
+/// Size of a WebAssembly page in bytes
+const WASM_PAGE_SIZE: u64 = 0x10000;
+
+impl ResourceLimiter for ModState {
+    fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> bool {
+        let Some(max_pages) = self.limits.max_memory_pages else {
+            return true;
+        };
+
+        if desired as u64 > max_pages.saturating_mul(WASM_PAGE_SIZE) {
+            warn!(
+                "Mod {:?} tried to grow its memory to {} bytes, but is limited to {} pages",
+                self.entity, desired, max_pages
+            );
+            return false;
+        }
+        true
+    }
+
+    fn table_growing(&mut self, _current: u32, desired: u32, _maximum: Option<u32>) -> bool {
+        let Some(max_elements) = self.limits.max_table_elements else {
+            return true;
+        };
+
+        if desired > max_elements {
+            warn!(
+                "Mod {:?} tried to grow a table to {} elements, but is limited to {}",
+                self.entity, desired, max_elements
+            );
+            return false;
+        }
+        true
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.max_instances.unwrap_or(DEFAULT_INSTANCE_LIMIT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Entity;
+    use wasmtime::{Engine, Instance, Module, Store};
+
+    use super::*;
+    use crate::limits::ModLimits;
+
+    fn limited(limits: ModLimits) -> ModState {
+        ModState {
+            limits,
+            ..ModState::new_for_test(Entity::from_raw(0))
+        }
+    }
+
+    #[test]
+    fn memory_is_capped() {
+        let mut mod_state = limited(ModLimits {
+            max_memory_pages: Some(2),
+            ..Default::default()
+        });
+
+        assert!(mod_state.memory_growing(0, 2 * WASM_PAGE_SIZE as usize, None));
+        assert!(!mod_state.memory_growing(0, 2 * WASM_PAGE_SIZE as usize + 1, None));
+    }
+
+    #[test]
+    fn tables_are_capped() {
+        let mut mod_state = limited(ModLimits {
+            max_table_elements: Some(10),
+            ..Default::default()
+        });
+
+        assert!(mod_state.table_growing(0, 10, None));
+        assert!(!mod_state.table_growing(0, 11, None));
+    }
+
+    #[test]
+    fn no_limits_allow_everything() {
+        let mut mod_state = limited(ModLimits::default());
+
+        assert!(mod_state.memory_growing(0, usize::MAX, None));
+        assert!(mod_state.table_growing(0, u32::MAX, None));
+        assert_eq!(mod_state.instances(), DEFAULT_INSTANCE_LIMIT);
+    }
+
+    #[test]
+    fn growing_past_the_cap_fails_inside_the_mod() {
+        let engine = Engine::default();
+        let module = Module::new(
+            &engine,
+            r#"
+                (module
+                    (memory 1)
+                    (func (export "grow") (param $pages i32) (result i32)
+                        (memory.grow (local.get $pages))))
+            "#,
+        )
+        .unwrap();
+
+        let mut store = Store::new(
+            &engine,
+            limited(ModLimits {
+                max_memory_pages: Some(2),
+                ..Default::default()
+            }),
+        );
+        store.limiter(|mod_state| mod_state);
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let grow = instance
+            .get_typed_func::<i32, i32>(&mut store, "grow")
+            .unwrap();
+
+        assert_eq!(grow.call(&mut store, 1).unwrap(), 1);
+        assert_eq!(grow.call(&mut store, 1).unwrap(), -1);
+    }
+}
@@ -2,13 +2,23 @@ use std::time::Instant;
 
 use anyhow::Result;
 use bevy::{prelude::*, utils::Uuid};
-use bevy_wasm_shared::prelude::*;
+use bevy_wasm_shared::{
+    batch, host_function::HostCallStatus, prelude::*, resource_update::RESOURCE_DENIED,
+};
 use colored::*;
 use wasmtime::*;
 
-use crate::mod_state::ModState;
+use crate::{
+    mod_state::ModState,
+    runtime::{mark_resource_read, request_resource_resync, store_resource_write, HostFunctions},
+};
 
-pub(crate) fn build_linker(engine: &Engine, protocol_version: Version) -> Result<Linker<ModState>> {
+pub(crate) fn build_linker(
+    engine: &Engine,
+    protocol_version: Version,
+    codec: Codec,
+    host_functions: HostFunctions,
+) -> Result<Linker<ModState>> {
     let mut linker: Linker<ModState> = Linker::new(engine);
 
     linker.func_wrap(
@@ -115,18 +125,144 @@ pub(crate) fn build_linker(engine: &Engine, protocol_version: Version) -> Result
                 _ => panic!("failed to find mod memory"),
             };
 
-            let Some(serialized_event) = caller.data_mut().events_in.pop_front() else { return 0 };
+            let Some(serialized_event) = caller.data().events_in.front().cloned() else { return 0 };
+            let event_len = serialized_event.len() as u32;
+
+            // Tell the mod how much space it needs without giving up the event
+            if event_len > len {
+                return event_len;
+            }
 
             let Some(buffer) = mem
                 .data_mut(&mut caller)
                 .get_mut(arena as u32 as usize..)
-                .and_then(|arr| arr.get_mut(..len as usize)) else {
+                .and_then(|arr| arr.get_mut(..event_len as usize)) else {
+                    error!("Failed to get data from memory");
+                    return 0;
+                };
+
+            buffer.copy_from_slice(&serialized_event);
+            caller.data_mut().events_in.pop_front();
+            event_len
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "send_serialized_events",
+        |mut caller: Caller<'_, ModState>, msg: i32, len: u32| {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            let Some(data) = mem
+                .data(&caller)
+                .get(msg as u32 as usize..)
+                .and_then(|arr| arr.get(..len as usize))
+                .map(|batch| batch::decode(batch).map(Box::from).collect::<Vec<_>>()) else {
+                    error!("Failed to get data from memory");
+                    return;
+                };
+
+            caller.data_mut().events_out.extend(data);
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "get_events",
+        |mut caller: Caller<'_, ModState>, arena: i32, len: u32| -> u32 {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            let events_in = &caller.data().events_in;
+            if events_in.is_empty() {
+                return 0;
+            }
+            let batch_len = batch::encoded_len(events_in.iter().map(|event| &event[..])) as u32;
+
+            // Tell the mod how much space it needs without giving up the events
+            if batch_len > len {
+                return batch_len;
+            }
+
+            let batch = batch::encode(events_in.iter().map(|event| &event[..]));
+
+            let Some(buffer) = mem
+                .data_mut(&mut caller)
+                .get_mut(arena as u32 as usize..)
+                .and_then(|arr| arr.get_mut(..batch_len as usize)) else {
+                    error!("Failed to get data from memory");
+                    return 0;
+                };
+
+            buffer.copy_from_slice(&batch);
+            caller.data_mut().events_in.clear();
+            batch_len
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "send_channel_events",
+        |mut caller: Caller<'_, ModState>, uuid_0: u64, uuid_1: u64, msg: i32, len: u32| {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            let Some(data) = mem
+                .data(&caller)
+                .get(msg as u32 as usize..)
+                .and_then(|arr| arr.get(..len as usize))
+                .map(|batch| batch::decode(batch).map(Box::from).collect::<Vec<_>>()) else {
+                    error!("Failed to get data from memory");
+                    return;
+                };
+
+            let uuid = Uuid::from_u64_pair(uuid_0, uuid_1);
+            caller.data_mut().channels_out.entry(uuid).or_default().extend(data);
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "get_channel_events",
+        |mut caller: Caller<'_, ModState>,
+         uuid_0: u64,
+         uuid_1: u64,
+         arena: i32,
+         len: u32|
+         -> u32 {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            let uuid = Uuid::from_u64_pair(uuid_0, uuid_1);
+            let Some(events_in) = caller.data().channels_in.get(&uuid) else { return 0 };
+            if events_in.is_empty() {
+                return 0;
+            }
+            let batch_len = batch::encoded_len(events_in.iter().map(|event| &event[..])) as u32;
+
+            // Tell the mod how much space it needs without giving up the events
+            if batch_len > len {
+                return batch_len;
+            }
+
+            let batch = batch::encode(events_in.iter().map(|event| &event[..]));
+
+            let Some(buffer) = mem
+                .data_mut(&mut caller)
+                .get_mut(arena as u32 as usize..)
+                .and_then(|arr| arr.get_mut(..batch_len as usize)) else {
                     error!("Failed to get data from memory");
                     return 0;
                 };
 
-            buffer[..serialized_event.len()].copy_from_slice(&serialized_event);
-            serialized_event.len() as u32
+            buffer.copy_from_slice(&batch);
+            caller.data_mut().channels_in.remove(&uuid);
+            batch_len
         },
     )?;
     linker.func_wrap(
@@ -144,23 +280,240 @@ pub(crate) fn build_linker(engine: &Engine, protocol_version: Version) -> Result
             };
 
             let uuid = Uuid::from_u64_pair(uuid_0, uuid_1);
-            let resource_bytes = caller.data_mut().shared_resource_values.remove(&uuid);
+            if caller.data().denied_resources.contains(&uuid) {
+                return RESOURCE_DENIED;
+            }
+            let resource_bytes = caller.data().shared_resource_values.get(&uuid).cloned();
 
             let resource_bytes = match resource_bytes {
                 Some(resource_bytes) => resource_bytes,
                 None => return 0,
             };
+            let len = resource_bytes.len() as u32;
+
+            // Tell the mod how much space it needs without marking the value as read
+            if len > buffer_len {
+                return len;
+            }
+
+            let Some(buffer) = mem
+                .data_mut(&mut caller)
+                .get_mut(buffer as u32 as usize..)
+                .and_then(|arr| arr.get_mut(..len as usize)) else {
+                    error!("Failed to get data from memory");
+                    return 0;
+                };
+
+            buffer.copy_from_slice(&resource_bytes);
+            mark_resource_read(caller.data_mut(), uuid);
+            len
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "request_resource_resync",
+        |mut caller: Caller<'_, ModState>, uuid_0: u64, uuid_1: u64| {
+            let uuid = Uuid::from_u64_pair(uuid_0, uuid_1);
+            request_resource_resync(caller.data_mut(), uuid);
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "store_resource",
+        |mut caller: Caller<'_, ModState>, uuid_0: u64, uuid_1: u64, value: i32, len: u32| {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            let Some(data) = mem
+                .data(&caller)
+                .get(value as u32 as usize..)
+                .and_then(|arr| arr.get(..len as usize))
+                .map(|x| x.into()) else {
+                    error!("Failed to get data from memory");
+                    return;
+                };
+
+            let uuid = Uuid::from_u64_pair(uuid_0, uuid_1);
+            store_resource_write(caller.data_mut(), uuid, data);
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "store_state",
+        |mut caller: Caller<'_, ModState>, state: i32, len: u32| {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            let Some(data) = mem
+                .data(&caller)
+                .get(state as u32 as usize..)
+                .and_then(|arr| arr.get(..len as usize))
+                .map(|x| x.into()) else {
+                    error!("Failed to get data from memory");
+                    return;
+                };
+
+            caller.data_mut().saved_state = Some(data);
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "get_saved_state",
+        |mut caller: Caller<'_, ModState>, buffer: i32, buffer_len: u32| -> u32 {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            let Some(saved_state) = caller.data_mut().saved_state.take() else { return 0 };
+            let len = saved_state.len() as u32;
+
+            // Tell the mod how much space it needs without giving up the state
+            if len > buffer_len {
+                caller.data_mut().saved_state = Some(saved_state);
+                return len;
+            }
+
+            let Some(buffer) = mem
+                .data_mut(&mut caller)
+                .get_mut(buffer as u32 as usize..)
+                .and_then(|arr| arr.get_mut(..len as usize)) else {
+                    error!("Failed to get data from memory");
+                    return 0;
+                };
+
+            buffer.copy_from_slice(&saved_state);
+            len
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "call_host_function",
+        move |mut caller: Caller<'_, ModState>,
+              name: i32,
+              name_len: u32,
+              args: i32,
+              args_len: u32|
+              -> u32 {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            caller.data_mut().host_function_result = None;
+
+            let data = mem.data(&caller);
+            let (Some(name), Some(args)) = (
+                data.get(name as u32 as usize..)
+                    .and_then(|arr| arr.get(..name_len as usize)),
+                data.get(args as u32 as usize..)
+                    .and_then(|arr| arr.get(..args_len as usize)),
+            ) else {
+                error!("Failed to get data from memory");
+                return HostCallStatus::Failed.to_u32();
+            };
+
+            let name = String::from_utf8_lossy(name);
+            let Some(function) = host_functions.get(name.as_ref()) else {
+                error!("Mod called unknown host function {}", name);
+                return HostCallStatus::UnknownFunction.to_u32();
+            };
+
+            match function(codec, args) {
+                Ok(result) => {
+                    caller.data_mut().host_function_result = Some(result.into());
+                    HostCallStatus::Ok.to_u32()
+                }
+                Err(err) => {
+                    error!("Error in host function {}: {}", name, err);
+                    HostCallStatus::Failed.to_u32()
+                }
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "get_host_function_result",
+        |mut caller: Caller<'_, ModState>, buffer: i32, buffer_len: u32| -> u32 {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            let Some(result) = caller.data_mut().host_function_result.take() else { return 0 };
+            let len = result.len() as u32;
+
+            // Tell the mod how much space it needs without giving up the result
+            if len > buffer_len {
+                caller.data_mut().host_function_result = Some(result);
+                return len;
+            }
 
             let Some(buffer) = mem
                 .data_mut(&mut caller)
                 .get_mut(buffer as u32 as usize..)
-                .and_then(|arr| arr.get_mut(..buffer_len as usize)) else {
+                .and_then(|arr| arr.get_mut(..len as usize)) else {
                     error!("Failed to get data from memory");
                     return 0;
                 };
 
-            buffer[..resource_bytes.len()].copy_from_slice(&resource_bytes);
-            resource_bytes.len() as u32
+            buffer.copy_from_slice(&result);
+            len
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "get_call_args",
+        |mut caller: Caller<'_, ModState>, buffer: i32, buffer_len: u32| -> u32 {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            let Some(args) = caller.data_mut().call_args.take() else { return 0 };
+            let len = args.len() as u32;
+
+            // Tell the mod how much space it needs without giving up the arguments
+            if len > buffer_len {
+                caller.data_mut().call_args = Some(args);
+                return len;
+            }
+
+            let Some(buffer) = mem
+                .data_mut(&mut caller)
+                .get_mut(buffer as u32 as usize..)
+                .and_then(|arr| arr.get_mut(..len as usize)) else {
+                    error!("Failed to get data from memory");
+                    return 0;
+                };
+
+            buffer.copy_from_slice(&args);
+            len
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "store_call_result",
+        |mut caller: Caller<'_, ModState>, result: i32, len: u32| {
+            let mem = match caller.get_export("memory") {
+                Some(Extern::Memory(mem)) => mem,
+                _ => panic!("failed to find mod memory"),
+            };
+
+            let Some(data) = mem
+                .data(&caller)
+                .get(result as u32 as usize..)
+                .and_then(|arr| arr.get(..len as usize))
+                .map(|x| x.into()) else {
+                    error!("Failed to get data from memory");
+                    return;
+                };
+
+            caller.data_mut().call_result = Some(data);
         },
     )?;
     linker.func_wrap(
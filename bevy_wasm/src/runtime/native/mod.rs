@@ -1,91 +1,687 @@
-use std::{collections::VecDeque, sync::Arc};
+use std::{collections::VecDeque, path::PathBuf, sync::Arc, task::Poll, time::Duration};
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use bevy::{
-    prelude::{Component, Resource},
-    utils::{HashMap, Instant},
+    asset::HandleId,
+    prelude::{debug, Component, Entity, Handle, Resource},
+    utils::{HashMap, HashSet, Instant, Uuid},
 };
-use bevy_wasm_shared::version::Version;
+use bevy_wasm_shared::{codec::Codec, version::Version};
+use serde::{de::DeserializeOwned, Serialize};
 use wasmtime::*;
 
-use crate::{mod_state::ModState, SharedResource};
+use crate::{
+    components::WasmMod,
+    error::{CallError, ModError},
+    limits::ModLimits,
+    mod_state::ModState,
+    shared_resources::ResourceAccess,
+    wasm_asset::WasmAsset,
+    SharedResource,
+};
+
+use super::{
+    check_codec, check_ffi_version, check_protocol_version, denied_resources, deny_resource,
+    next_instance_id, request_resource_resync, HostFunctions, VersionChecker,
+};
+
+use self::{
+    epoch_ticker::{EpochTicker, NO_DEADLINE},
+    linker::build_linker,
+    module_cache::ModuleCache,
+};
 
-use self::linker::build_linker;
+/// Fuel given to calls without a budget. Far more than a single call can burn through,
+/// but far from the maximum, which wasmtime can't add to once a mod ran out of fuel.
+const UNLIMITED_FUEL: u64 = u64::MAX >> 16;
 
+mod epoch_ticker;
+mod limiter;
 mod linker;
+mod module_cache;
+#[cfg(test)]
+pub(crate) mod test_mods;
 
 #[derive(Resource)]
 pub struct WasmRuntime {
     engine: Engine,
+    linker: Linker<ModState>,
+    /// Modules that have already been compiled and linked, by the handle of their asset
+    modules: HashMap<HandleId, InstancePre<ModState>>,
     protocol_version: Version,
+    limits: ModLimits,
+    version_checker: VersionChecker,
+    /// Whether mods that don't export their protocol version are loaded anyway
+    allow_unversioned_mods: bool,
+    module_cache: Option<ModuleCache>,
+    codec: Codec,
+    /// The latest value of every shared resource, to seed new instances with
+    shared_resource_values: HashMap<Uuid, Arc<[u8]>>,
+    /// Which mods may read each restricted shared resource
+    resource_access: HashMap<Uuid, ResourceAccess>,
+    /// Shared resources mods may write to
+    writable_resources: HashSet<Uuid>,
+    _epoch_ticker: EpochTicker,
 }
 
 impl WasmRuntime {
-    pub fn new(protocol_version: Version) -> Self {
+    pub fn new(
+        protocol_version: Version,
+        limits: ModLimits,
+        version_checker: VersionChecker,
+        module_cache: Option<PathBuf>,
+        codec: Codec,
+        host_functions: HostFunctions,
+        resource_access: HashMap<Uuid, ResourceAccess>,
+    ) -> Self {
+        let mut config = Config::new();
+        config.consume_fuel(true).epoch_interruption(true);
+
+        let engine = Engine::new(&config).expect("Failed to create the wasmtime engine");
+        let linker = build_linker(&engine, protocol_version, codec, host_functions)
+            .expect("Failed to build a linker for bevy_wasm");
+        let module_cache = module_cache
+            .map(|dir| ModuleCache::new(dir, &engine).expect("Failed to create the module cache"));
+
         Self {
-            engine: Engine::default(),
+            _epoch_ticker: EpochTicker::new(engine.clone()),
+            engine,
+            linker,
+            modules: HashMap::new(),
             protocol_version,
+            limits,
+            version_checker,
+            allow_unversioned_mods: false,
+            module_cache,
+            codec,
+            shared_resource_values: HashMap::new(),
+            resource_access,
+            writable_resources: HashSet::new(),
+        }
+    }
+
+    /// Compile and link a mod's module, or reuse it if another mod was created from the same asset
+    ///
+    /// The linker only defines host functions that don't belong to any store,
+    /// so the result can be instantiated in every mod's store.
+    fn instance_pre(
+        &mut self,
+        store: &mut Store<ModState>,
+        handle: &Handle<WasmAsset>,
+        wasm_bytes: &[u8],
+    ) -> Result<InstancePre<ModState>> {
+        if let Some(instance_pre) = self.modules.get(&handle.id()) {
+            return Ok(instance_pre.clone());
+        }
+
+        let module = match &self.module_cache {
+            Some(module_cache) => module_cache.load_or_compile(&self.engine, wasm_bytes)?,
+            None => Module::new(&self.engine, wasm_bytes)?,
+        };
+        let instance_pre = self.linker.instantiate_pre(store, &module)?;
+        self.modules.insert(handle.id(), instance_pre.clone());
+
+        Ok(instance_pre)
+    }
+
+    /// Forget the compiled module of an asset that was modified or removed
+    pub fn forget_module(&mut self, handle: &Handle<WasmAsset>) {
+        if self.modules.remove(&handle.id()).is_some() {
+            debug!("Forgot compiled module {:?}", handle.id());
         }
     }
 
-    pub fn create_instance(&self, wasm_bytes: &[u8]) -> Result<WasmInstance> {
+    /// Remember the latest value of a shared resource, so new instances start with it
+    pub fn set_shared_resource_value<T: SharedResource>(&mut self, bytes: Arc<[u8]>) {
+        self.shared_resource_values.insert(T::TYPE_UUID, bytes);
+    }
+
+    /// Forget the value of a shared resource the game removed, so new instances start without it
+    pub fn remove_shared_resource_value<T: SharedResource>(&mut self) {
+        self.shared_resource_values.remove(&T::TYPE_UUID);
+    }
+
+    /// Load mods that don't export their protocol version, assuming they are compatible
+    pub fn allow_unversioned_mods(&mut self) {
+        self.allow_unversioned_mods = true;
+    }
+
+    /// Let mods write to the shared resource `T`. Writes to other resources are dropped.
+    pub fn allow_resource_writes<T: SharedResource>(&mut self) {
+        self.writable_resources.insert(T::TYPE_UUID);
+    }
+
+    /// Whether the mod may read the shared resource `T`
+    pub fn may_read_resource<T: SharedResource>(&self, entity: Entity, wasm_mod: &WasmMod) -> bool {
+        self.resource_access
+            .get(&T::TYPE_UUID)
+            .is_none_or(|access| access.allows(entity, wasm_mod))
+    }
+
+    /// The latest value of every shared resource the mod may read, and the resources it may not
+    fn seed_resources(
+        &self,
+        entity: Entity,
+        wasm_mod: &WasmMod,
+    ) -> (HashMap<Uuid, Arc<[u8]>>, HashSet<Uuid>) {
+        let denied = denied_resources(&self.resource_access, entity, wasm_mod);
+        let values = self
+            .shared_resource_values
+            .iter()
+            .filter(|(uuid, _)| !denied.contains(*uuid))
+            .map(|(uuid, bytes)| (*uuid, bytes.clone()))
+            .collect();
+        (values, denied)
+    }
+
+    pub fn create_instance(
+        &mut self,
+        entity: Entity,
+        wasm_mod: &WasmMod,
+        wasm_bytes: &[u8],
+    ) -> Result<WasmInstance> {
+        let (shared_resource_values, denied_resources) = self.seed_resources(entity, wasm_mod);
+
         // Create store and instance
-        let module = Module::new(&self.engine, wasm_bytes)?;
         let mut store = Store::new(
             &self.engine,
             ModState {
+                entity,
+                instance_id: next_instance_id(),
+                codec: self.codec,
+                limits: wasm_mod.limits.unwrap_or(self.limits),
                 startup_time: Instant::now(),
                 app_ptr: 0,
                 events_out: Vec::new(),
+                channels_in: HashMap::new(),
+                channels_out: HashMap::new(),
                 events_in: VecDeque::new(),
-                shared_resource_values: HashMap::new(),
+                shared_resource_values,
+                resource_versions: HashMap::new(),
+                resource_resyncs: HashSet::new(),
+                denied_resources,
+                writable_resources: self.writable_resources.clone(),
+                resource_writes: HashMap::new(),
+                host_function_result: None,
+                call_args: None,
+                call_result: None,
+                saved_state: None,
             },
         );
-        let instance = build_linker(&self.engine, self.protocol_version)
-            .context("Failed to build a linker for bevy_wasm")?
-            .module(&mut store, "", &module)?
-            .instantiate(&mut store, &module)?;
+
+        store.limiter(|mod_state| mod_state);
+
+        // The fuel budget only applies to `update`, so `build_app` gets unlimited fuel
+        store.add_fuel(UNLIMITED_FUEL)?;
+        set_deadline(&mut store, wasm_mod.into());
+
+        let instance = self
+            .instance_pre(&mut store, &wasm_mod.wasm, wasm_bytes)?
+            .instantiate(&mut store)?;
+
+        // Call `extern "C" fn get_mod_ffi_version` and `get_mod_protocol_version` before letting
+        // the mod build its app
+        let mod_ffi_version = match instance.get_func(&mut store, "get_mod_ffi_version") {
+            Some(func) => Some(
+                func.typed::<(), u32>(&store)?
+                    .call(&mut store, ())
+                    .map_err(|err| {
+                        trap_to_error(
+                            err,
+                            entity,
+                            wasm_mod.into(),
+                            "Failed to call get_mod_ffi_version",
+                        )
+                    })?,
+            ),
+            None => None,
+        };
+        check_ffi_version(entity, mod_ffi_version)?;
+
+        let mod_version = match instance.get_func(&mut store, "get_mod_protocol_version") {
+            Some(func) => {
+                let version = func
+                    .typed::<(), u64>(&store)?
+                    .call(&mut store, ())
+                    .map_err(|err| {
+                        trap_to_error(
+                            err,
+                            entity,
+                            wasm_mod.into(),
+                            "Failed to call get_mod_protocol_version",
+                        )
+                    })?;
+                Some(Version::from_u64(version))
+            }
+            None => None,
+        };
+        check_protocol_version(
+            &self.version_checker,
+            self.allow_unversioned_mods,
+            entity,
+            self.protocol_version,
+            mod_version,
+        )?;
 
         // Call `extern "C" fn build_app`
         instance
             .get_typed_func::<(), ()>(&mut store, "build_app")?
             .call(&mut store, ())
-            .context("Failed to call build_app")?;
+            .map_err(|err| {
+                trap_to_error(err, entity, wasm_mod.into(), "Failed to call build_app")
+            })?;
+        if store.data().app_ptr == 0 {
+            return Err(ModError::AppNotBuilt { entity }.into());
+        }
 
-        Ok(WasmInstance { instance, store })
+        // Call `extern "C" fn get_mod_codec` now that the mod set it
+        let mod_codec = match instance.get_func(&mut store, "get_mod_codec") {
+            Some(func) => Some(
+                func.typed::<(), u32>(&store)?
+                    .call(&mut store, ())
+                    .map_err(|err| {
+                        trap_to_error(err, entity, wasm_mod.into(), "Failed to call get_mod_codec")
+                    })?,
+            ),
+            None => None,
+        };
+        check_codec(entity, self.codec, mod_codec)?;
+
+        Ok(WasmInstance {
+            instance,
+            store,
+            budget: wasm_mod.into(),
+        })
     }
 }
 
+/// A running instance of a [`WasmMod`], added to its entity once it is loaded
+///
+/// Use it to call the functions the mod exported, see [`WasmInstance::call`].
 #[derive(Component)]
 pub struct WasmInstance {
     instance: Instance,
     store: Store<ModState>,
+    /// The budget the mod was last ticked with, also used for [`WasmInstance::call`]
+    budget: Budget,
 }
 
 impl WasmInstance {
+    /// Whether `build_app` has finished. Native instances are created synchronously.
+    pub(crate) fn poll_loaded(&self) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
     /// Tick the internal mod state
-    pub(crate) fn tick(&mut self, events_in: &[Arc<[u8]>]) -> Result<Vec<Box<[u8]>>> {
+    pub(crate) fn tick(
+        &mut self,
+        wasm_mod: &WasmMod,
+        events_in: &[Arc<[u8]>],
+    ) -> Result<Vec<Box<[u8]>>> {
         for event in events_in.iter() {
             self.store.data_mut().events_in.push_back(event.clone());
         }
 
+        self.budget = wasm_mod.into();
+        self.refuel(wasm_mod.fuel)?;
+        set_deadline(&mut self.store, self.budget);
+
         let app_ptr = self.store.data().app_ptr;
 
         // Call `extern "C" fn update`
-        self.instance
+        let result = self
+            .instance
             .get_typed_func::<i32, ()>(&mut self.store, "update")?
-            .call(&mut self.store, app_ptr)
-            .context("Failed to call update")?;
+            .call(&mut self.store, app_ptr);
+
+        if let Err(err) = result {
+            // Whatever the mod sent before it trapped is thrown away, and so is what it didn't
+            // get to read, so it isn't handed the same events again on its next tick
+            let state = self.store.data_mut();
+            state.events_out.clear();
+            state.channels_out.clear();
+            state.resource_writes.clear();
+            state.events_in.clear();
+            state.channels_in.clear();
+
+            let entity = self.store.data().entity;
+            return Err(trap_to_error(
+                err,
+                entity,
+                wasm_mod.into(),
+                "Failed to call update",
+            ));
+        }
+
+        // Mods that don't use a channel never read it, so don't keep its messages around
+        self.store.data_mut().channels_in.clear();
 
         let serialized_events_out = std::mem::take(&mut self.store.data_mut().events_out);
 
         Ok(serialized_events_out)
     }
 
-    /// Update the value of a shared resource as seen by the mod
-    pub fn update_resource_value<T: SharedResource>(&mut self, bytes: Arc<[u8]>) {
+    /// Unique to this instance of the mod, unlike its entity
+    pub(crate) fn id(&self) -> u64 {
+        self.store.data().instance_id
+    }
+
+    /// Queue events on an additional channel for the mod's next tick. The mod must read them
+    /// during that tick, they are dropped after it.
+    pub(crate) fn push_channel_events(&mut self, channel: Uuid, events: &[Arc<[u8]>]) {
+        let channel = self
+            .store
+            .data_mut()
+            .channels_in
+            .entry(channel)
+            .or_default();
+        channel.extend(events.iter().cloned());
+    }
+
+    /// Take the events the mod sent on an additional channel
+    pub(crate) fn take_channel_events(&mut self, channel: Uuid) -> Vec<Box<[u8]>> {
+        self.store
+            .data_mut()
+            .channels_out
+            .remove(&channel)
+            .unwrap_or_default()
+    }
+
+    /// Call a function the mod exported with `#[bevy_wasm_sys::export]`, and return its result
+    ///
+    /// Arguments and results are serialized with the plugin's codec. The call gets the fuel budget
+    /// and deadline the mod was last ticked with. A mod that fails during a call is not marked as
+    /// [`Failed`](crate::components::WasmModStatus::Failed), that is up to the caller.
+    pub fn call<Args, Ret>(&mut self, name: &str, args: &Args) -> Result<Ret, CallError>
+    where
+        Args: Serialize + ?Sized,
+        Ret: DeserializeOwned,
+    {
+        let export = self
+            .instance
+            .get_func(&mut self.store, name)
+            .and_then(|export| export.typed::<(), ()>(&self.store).ok())
+            .ok_or_else(|| CallError::MissingExport(name.to_string()))?;
+
+        let ModState { entity, codec, .. } = *self.store.data();
+        let args = codec.encode(args)?;
+
+        self.refuel(self.budget.fuel).map_err(CallError::Failed)?;
+        set_deadline(&mut self.store, self.budget);
+
+        self.store.data_mut().call_args = Some(args.into());
+        let result = export.call(&mut self.store, ());
+        self.store.data_mut().call_args = None;
+        let call_result = self.store.data_mut().call_result.take();
+
+        result.map_err(|err| {
+            CallError::Failed(trap_to_error(
+                err,
+                entity,
+                self.budget,
+                "Failed to call export",
+            ))
+        })?;
+        let call_result = call_result.ok_or(CallError::NoResult)?;
+
+        Ok(codec.decode(&call_result)?)
+    }
+
+    /// Call the mod's `save_state` export, if it has one, and return what it saved
+    pub(crate) fn save_state(&mut self, wasm_mod: &WasmMod) -> Result<Option<Box<[u8]>>> {
+        let Some(save_state) = self.instance.get_func(&mut self.store, "save_state") else {
+            return Ok(None);
+        };
+
+        self.refuel(wasm_mod.fuel)?;
+        set_deadline(&mut self.store, wasm_mod.into());
+
+        let ModState {
+            entity, app_ptr, ..
+        } = *self.store.data();
+        save_state
+            .typed::<i32, ()>(&self.store)?
+            .call(&mut self.store, app_ptr)
+            .map_err(|err| {
+                trap_to_error(err, entity, wasm_mod.into(), "Failed to call save_state")
+            })?;
+
+        Ok(self.store.data_mut().saved_state.take())
+    }
+
+    /// Hand state saved by a previous instance to the mod's `restore_state` export, if it has one
+    pub(crate) fn restore_state(&mut self, wasm_mod: &WasmMod, state: Box<[u8]>) -> Result<()> {
+        let Some(restore_state) = self.instance.get_func(&mut self.store, "restore_state") else {
+            return Ok(());
+        };
+
+        self.refuel(wasm_mod.fuel)?;
+        set_deadline(&mut self.store, wasm_mod.into());
+
+        self.store.data_mut().saved_state = Some(state);
+        let ModState {
+            entity, app_ptr, ..
+        } = *self.store.data();
+        let result = restore_state
+            .typed::<i32, ()>(&self.store)?
+            .call(&mut self.store, app_ptr);
+        self.store.data_mut().saved_state = None;
+
+        result.map_err(|err| {
+            trap_to_error(err, entity, wasm_mod.into(), "Failed to call restore_state")
+        })
+    }
+
+    /// Set the remaining fuel to exactly `fuel`, or to unlimited if `None`
+    fn refuel(&mut self, fuel: Option<u64>) -> Result<()> {
+        let fuel = fuel.unwrap_or(UNLIMITED_FUEL);
+        // A mod that ran out of fuel has overdrawn it slightly, which `consume_fuel` reports as an error
+        let remaining = self.store.consume_fuel(0).unwrap_or(0);
+        if remaining < fuel {
+            self.store.add_fuel(fuel - remaining)?;
+        } else {
+            self.store.consume_fuel(remaining - fuel)?;
+        }
+        Ok(())
+    }
+
+    /// Update the value of a shared resource as seen by the mod, with a serialized `ResourceUpdate`
+    pub(crate) fn update_resource_value<T: SharedResource>(&mut self, bytes: Arc<[u8]>) {
         let state = self.store.data_mut();
 
         state.shared_resource_values.insert(T::TYPE_UUID, bytes);
     }
+
+    /// Version of the last value of a shared resource the mod read
+    pub(crate) fn resource_version<T: SharedResource>(&self) -> Option<u64> {
+        self.store
+            .data()
+            .resource_versions
+            .get(&T::TYPE_UUID)
+            .copied()
+    }
+
+    /// Send the whole value of a shared resource to the mod on its next update
+    pub(crate) fn request_resource_resync<T: SharedResource>(&mut self) {
+        request_resource_resync(self.store.data_mut(), T::TYPE_UUID);
+    }
+
+    /// Whether the mod needs the whole value of a shared resource, even if it didn't change
+    pub(crate) fn take_resource_resync<T: SharedResource>(&mut self) -> bool {
+        self.store.data_mut().resource_resyncs.remove(&T::TYPE_UUID)
+    }
+
+    /// Stop sending a shared resource to the mod, and report its reads as denied
+    pub(crate) fn deny_resource<T: SharedResource>(&mut self) {
+        deny_resource(self.store.data_mut(), T::TYPE_UUID);
+    }
+
+    /// Let the mod read a shared resource again. Returns whether it was denied until now.
+    pub(crate) fn allow_resource<T: SharedResource>(&mut self) -> bool {
+        self.store.data_mut().denied_resources.remove(&T::TYPE_UUID)
+    }
+
+    /// Take the value the mod wrote to a shared resource since it was last taken
+    pub(crate) fn take_resource_write<T: SharedResource>(&mut self) -> Option<Box<[u8]>> {
+        self.store.data_mut().resource_writes.remove(&T::TYPE_UUID)
+    }
+
+    /// The mod's state, for tests to look at
+    #[cfg(test)]
+    pub(crate) fn mod_state(&self) -> &ModState {
+        self.store.data()
+    }
+}
+
+/// The fuel budget and deadline of a single call into the mod, from its [`WasmMod`]
+#[derive(Clone, Copy)]
+struct Budget {
+    fuel: Option<u64>,
+    deadline: Option<Duration>,
+}
+
+impl From<&WasmMod> for Budget {
+    fn from(wasm_mod: &WasmMod) -> Self {
+        Self {
+            fuel: wasm_mod.fuel,
+            deadline: wasm_mod.deadline,
+        }
+    }
+}
+
+/// Give the next call into the mod its wall-clock deadline
+fn set_deadline(store: &mut Store<ModState>, budget: Budget) {
+    let ticks = budget.deadline.map_or(NO_DEADLINE, EpochTicker::ticks);
+    store.set_epoch_deadline(ticks);
+}
+
+/// Turn budget and deadline traps into a [`ModError`]
+fn trap_to_error(
+    err: anyhow::Error,
+    entity: Entity,
+    budget: Budget,
+    context: &'static str,
+) -> anyhow::Error {
+    match (err.downcast_ref::<Trap>(), budget.fuel, budget.deadline) {
+        (Some(Trap::OutOfFuel), Some(fuel), _) => ModError::BudgetExceeded { entity, fuel }.into(),
+        (Some(Trap::Interrupt), _, Some(deadline)) => {
+            ModError::DeadlineExceeded { entity, deadline }.into()
+        }
+        _ => err.context(context),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mod whose `update` loops as many times as its app pointer says
+    const SPINNING_MOD: &str = r#"
+        (module
+            (func (export "update") (param $n i32)
+                (loop $spin
+                    (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br_if $spin (i32.gt_s (local.get $n) (i32.const 0))))))
+    "#;
+
+    fn spinning_instance() -> WasmInstance {
+        let mut config = Config::new();
+        config.consume_fuel(true).epoch_interruption(true);
+        let engine = Engine::new(&config).unwrap();
+        let module = Module::new(&engine, SPINNING_MOD).unwrap();
+
+        let mut store = Store::new(&engine, ModState::new_for_test(Entity::from_raw(0)));
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+
+        WasmInstance {
+            instance,
+            store,
+            budget: Budget {
+                fuel: None,
+                deadline: None,
+            },
+        }
+    }
+
+    #[test]
+    fn mods_tick_again_after_running_out_of_fuel() {
+        let mut instance = spinning_instance();
+        let budgeted = WasmMod::new(Handle::default()).with_fuel(1_000);
+        let unlimited = WasmMod::new(Handle::default());
+
+        instance.store.data_mut().app_ptr = 1_000_000;
+        let err = instance.tick(&budgeted, &[]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ModError::BudgetExceeded { fuel: 1_000, .. })
+        ));
+
+        instance.store.data_mut().app_ptr = 10;
+        instance.tick(&budgeted, &[]).unwrap();
+
+        instance.store.data_mut().app_ptr = 1_000_000;
+        instance.tick(&budgeted, &[]).unwrap_err();
+        instance.tick(&unlimited, &[]).unwrap();
+    }
+
+    #[test]
+    fn mods_are_interrupted_at_their_deadline() {
+        let mut instance = spinning_instance();
+        let _epoch_ticker = EpochTicker::new(instance.store.engine().clone());
+        let wasm_mod = WasmMod::new(Handle::default()).with_deadline(Duration::from_millis(10));
+
+        instance.store.data_mut().app_ptr = i32::MAX;
+        let err = instance.tick(&wasm_mod, &[]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ModError::DeadlineExceeded { .. })
+        ));
+
+        instance.store.data_mut().app_ptr = 10;
+        instance.tick(&wasm_mod, &[]).unwrap();
+    }
+
+    #[test]
+    fn mods_from_the_same_asset_share_their_module() {
+        let mut runtime = test_mods::runtime();
+        let wasm = test_mods::echo_mod();
+        let handle = test_mods::wasm_handle();
+
+        for index in 0..3 {
+            let wasm_mod = WasmMod::new(handle.clone());
+            runtime
+                .create_instance(Entity::from_raw(index), &wasm_mod, wasm.as_bytes())
+                .unwrap();
+        }
+        assert_eq!(runtime.modules.len(), 1);
+
+        let other_mod = WasmMod::new(test_mods::wasm_handle());
+        runtime
+            .create_instance(Entity::from_raw(3), &other_mod, wasm.as_bytes())
+            .unwrap();
+        assert_eq!(runtime.modules.len(), 2);
+
+        runtime.forget_module(&handle);
+        assert_eq!(runtime.modules.len(), 1);
+    }
+
+    #[test]
+    fn unread_channel_messages_are_dropped_after_a_tick() {
+        let mut runtime = test_mods::runtime();
+        let wasm_mod = WasmMod::new(test_mods::wasm_handle());
+        let mut instance = runtime
+            .create_instance(
+                Entity::from_raw(0),
+                &wasm_mod,
+                test_mods::echo_mod().as_bytes(),
+            )
+            .unwrap();
+
+        let channel = Uuid::from_u128(1);
+        instance.push_channel_events(channel, &[Arc::from(&b"unread"[..])]);
+        assert!(!instance.store.data().channels_in.is_empty());
+
+        instance.tick(&wasm_mod, &[]).unwrap();
+        assert!(instance.store.data().channels_in.is_empty());
+    }
 }
@@ -0,0 +1,127 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use bevy::prelude::{info, warn};
+use sha2::{Digest, Sha256};
+use wasmtime::{Engine, Module};
+
+/// The smallest valid WebAssembly module
+const EMPTY_MODULE: &[u8] = b"\0asm\x01\0\0\0";
+
+/// Compiled modules stored on disk, keyed by a hash of their WebAssembly binary
+/// and of the engine that compiled them
+pub struct ModuleCache {
+    dir: PathBuf,
+    /// Hash of the wasmtime version and engine configuration
+    engine_hash: [u8; 32],
+}
+
+impl ModuleCache {
+    pub fn new(dir: PathBuf, engine: &Engine) -> Result<Self> {
+        // Precompiled modules start with the wasmtime version and the settings they were
+        // compiled with, so an empty one tells engines apart
+        let engine_hash = Sha256::digest(engine.precompile_module(EMPTY_MODULE)?).into();
+        Ok(Self { dir, engine_hash })
+    }
+
+    /// Load a compiled module from the cache, or compile it and store it on a miss
+    pub fn load_or_compile(&self, engine: &Engine, wasm_bytes: &[u8]) -> Result<Module> {
+        let key = format!(
+            "{:x}",
+            Sha256::new()
+                .chain_update(self.engine_hash)
+                .chain_update(wasm_bytes)
+                .finalize()
+        );
+        let path = self.dir.join(key).with_extension("cwasm");
+
+        if path.exists() {
+            // SAFETY: The cache directory only contains the output of `Module::serialize`.
+            // Modules compiled by a different wasmtime version or engine configuration are rejected.
+            match unsafe { Module::deserialize_file(engine, &path) } {
+                Ok(module) => {
+                    info!("Module cache hit: {}", path.display());
+                    return Ok(module);
+                }
+                Err(err) => warn!(
+                    "Ignoring cached module {} that doesn't match the engine: {:#}",
+                    path.display(),
+                    err
+                ),
+            }
+        }
+
+        info!("Module cache miss: {}", path.display());
+        let module = Module::new(engine, wasm_bytes)?;
+
+        if let Err(err) = store(&path, &module) {
+            warn!(
+                "Could not write {} to the module cache: {:#}",
+                path.display(),
+                err
+            );
+        }
+
+        Ok(module)
+    }
+}
+
+/// Write the compiled module next to its final path first, so a crash can't leave half a file behind
+fn store(path: &Path, module: &Module) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tmp_path = path.with_extension("cwasm.tmp");
+    fs::write(&tmp_path, module.serialize()?)?;
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmtime::Config;
+
+    use super::*;
+
+    fn engine(consume_fuel: bool) -> Engine {
+        let mut config = Config::new();
+        config.consume_fuel(consume_fuel);
+        Engine::new(&config).unwrap()
+    }
+
+    fn cached_files(dir: &Path) -> usize {
+        fs::read_dir(dir).map_or(0, |entries| entries.count())
+    }
+
+    #[test]
+    fn engines_are_told_apart() {
+        let dir = std::env::temp_dir().join("bevy_wasm_engine_hash");
+
+        let engine_hash = |engine| ModuleCache::new(dir.clone(), &engine).unwrap().engine_hash;
+        assert_eq!(engine_hash(engine(true)), engine_hash(engine(true)));
+        assert_ne!(engine_hash(engine(true)), engine_hash(engine(false)));
+    }
+
+    #[test]
+    fn modules_are_cached_per_engine() {
+        let dir =
+            std::env::temp_dir().join(format!("bevy_wasm_module_cache_{}", std::process::id()));
+        _ = fs::remove_dir_all(&dir);
+
+        for consume_fuel in [true, false] {
+            let engine = engine(consume_fuel);
+            let module_cache = ModuleCache::new(dir.clone(), &engine).unwrap();
+            module_cache.load_or_compile(&engine, EMPTY_MODULE).unwrap();
+            module_cache.load_or_compile(&engine, EMPTY_MODULE).unwrap();
+        }
+        let files = cached_files(&dir);
+        _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(files, 2);
+    }
+}
@@ -0,0 +1,73 @@
+//! Minimal mods written in the WebAssembly text format, for tests
+
+use bevy::{
+    asset::HandleId,
+    prelude::{Handle, World},
+    utils::HashMap,
+};
+use bevy_wasm_shared::version::{default_version_checker, FFI_VERSION};
+
+use super::*;
+use crate::components::WasmModStatus;
+
+pub const PROTOCOL_VERSION: Version = Version {
+    name_hash: 1,
+    major: 1,
+    minor: 0,
+    patch: 0,
+};
+
+/// A mod that sends every message it gets straight back, and reads nothing else
+pub fn echo_mod() -> String {
+    format!(
+        r#"
+            (module
+                (import "host" "store_app" (func $store_app (param i32)))
+                (import "host" "get_events" (func $get_events (param i32 i32) (result i32)))
+                (import "host" "send_serialized_events" (func $send_events (param i32 i32)))
+                (memory (export "memory") 1)
+                (func (export "get_mod_ffi_version") (result i32) (i32.const {ffi_version}))
+                (func (export "get_mod_protocol_version") (result i64) (i64.const {protocol_version}))
+                (func (export "get_mod_codec") (result i32) (i32.const {codec}))
+                (func (export "build_app") (call $store_app (i32.const 1)))
+                (func (export "update") (param $app i32) (local $len i32)
+                    (local.set $len (call $get_events (i32.const 0) (i32.const 65536)))
+                    (if (i32.gt_u (local.get $len) (i32.const 0))
+                        (then (call $send_events (i32.const 0) (local.get $len))))))
+        "#,
+        ffi_version = FFI_VERSION,
+        protocol_version = PROTOCOL_VERSION.to_u64() as i64,
+        codec = Codec::default().to_u8(),
+    )
+}
+
+/// A runtime with the default settings and no host functions
+pub fn runtime() -> WasmRuntime {
+    WasmRuntime::new(
+        PROTOCOL_VERSION,
+        ModLimits::default(),
+        Arc::new(default_version_checker),
+        None,
+        Codec::default(),
+        Arc::new(HashMap::new()),
+        HashMap::new(),
+    )
+}
+
+/// A handle to an asset that doesn't exist, standing in for the mod's wasm file
+pub fn wasm_handle() -> Handle<WasmAsset> {
+    Handle::weak(HandleId::random::<WasmAsset>())
+}
+
+/// Spawn a running mod created from `wasm`
+pub fn spawn_mod(world: &mut World, runtime: &mut WasmRuntime, wasm: &str) -> Entity {
+    let entity = world.spawn_empty().id();
+    let wasm_mod = WasmMod::new(wasm_handle());
+    let instance = runtime
+        .create_instance(entity, &wasm_mod, wasm.as_bytes())
+        .unwrap();
+    world
+        .entity_mut(entity)
+        .insert((wasm_mod, WasmModStatus::Running, instance));
+    entity
+}
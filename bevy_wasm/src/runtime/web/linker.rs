@@ -4,7 +4,10 @@ use bevy::{
     prelude::{error, info, warn},
     utils::Uuid,
 };
-use bevy_wasm_shared::version::Version;
+use bevy_wasm_shared::{
+    batch, codec::Codec, host_function::HostCallStatus, resource_update::RESOURCE_DENIED,
+    version::Version,
+};
 use colored::*;
 use js_sys::{Object, Reflect, Uint8Array, WebAssembly};
 use wasm_bindgen::{
@@ -12,7 +15,10 @@ use wasm_bindgen::{
     prelude::{Closure, JsValue},
 };
 
-use crate::mod_state::ModState;
+use crate::{
+    mod_state::ModState,
+    runtime::{mark_resource_read, request_resource_resync, store_resource_write, HostFunctions},
+};
 
 fn link<T>(target: &JsValue, name: &str, closure: impl IntoWasmClosure<T> + 'static)
 where
@@ -26,6 +32,8 @@ where
 #[allow(clippy::redundant_clone)]
 pub fn build_linker(
     protocol_version: Version,
+    codec: Codec,
+    host_functions: HostFunctions,
     mod_state: Arc<RwLock<ModState>>,
     memory: Arc<RwLock<Option<WebAssembly::Memory>>>,
 ) -> Object {
@@ -87,19 +95,21 @@ pub fn build_linker(
         let mod_state = mod_state.clone();
         let memory = memory.clone();
         move |ptr: i32, len: u32| -> u32 {
-            let next_event = mod_state.write().unwrap().events_in.pop_front();
-            if let Some(next_event) = next_event {
-                if next_event.len() > len as usize {
-                    error!("Serialized event is too long");
-                    return 0;
-                }
-                let arr = Uint8Array::from(&next_event[..]);
-                if let Some(memory) = memory.read().unwrap().as_ref() {
-                    Uint8Array::new(&memory.buffer()).set(&arr, ptr as u32);
-                    next_event.len() as u32
-                } else {
-                    0
-                }
+            let mut mod_state = mod_state.write().unwrap();
+            let Some(next_event) = mod_state.events_in.front() else { return 0 };
+
+            // Tell the mod how much space it needs without giving up the event
+            if next_event.len() > len as usize {
+                return next_event.len() as u32;
+            }
+
+            let arr = Uint8Array::from(&next_event[..]);
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                Uint8Array::new(&memory.buffer()).set(&arr, ptr as u32);
+                mod_state
+                    .events_in
+                    .pop_front()
+                    .map_or(0, |event| event.len() as u32)
             } else {
                 0
             }
@@ -119,6 +129,99 @@ pub fn build_linker(
         }
     });
 
+    link::<dyn FnMut(i32, u32)>(&host, "send_serialized_events", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |ptr, len| {
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                let buffer = Uint8Array::new(&memory.buffer())
+                    .slice(ptr as u32, ptr as u32 + len)
+                    .to_vec();
+                mod_state
+                    .write()
+                    .unwrap()
+                    .events_out
+                    .extend(batch::decode(&buffer).map(Box::from));
+            }
+        }
+    });
+
+    link::<dyn FnMut(i32, u32) -> u32>(&host, "get_events", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |ptr: i32, len: u32| -> u32 {
+            let mut mod_state = mod_state.write().unwrap();
+            if mod_state.events_in.is_empty() {
+                return 0;
+            }
+            let batch_len = batch::encoded_len(mod_state.events_in.iter().map(|event| &event[..]));
+
+            // Tell the mod how much space it needs without giving up the events
+            if batch_len > len as usize {
+                return batch_len as u32;
+            }
+
+            let batch = batch::encode(mod_state.events_in.iter().map(|event| &event[..]));
+            let arr = Uint8Array::from(&batch[..]);
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                Uint8Array::new(&memory.buffer()).set(&arr, ptr as u32);
+                mod_state.events_in.clear();
+                batch_len as u32
+            } else {
+                0
+            }
+        }
+    });
+
+    link::<dyn FnMut(u64, u64, i32, u32)>(&host, "send_channel_events", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |uuid_0, uuid_1, ptr, len| {
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                let buffer = Uint8Array::new(&memory.buffer())
+                    .slice(ptr as u32, ptr as u32 + len)
+                    .to_vec();
+                let uuid = Uuid::from_u64_pair(uuid_0, uuid_1);
+                mod_state
+                    .write()
+                    .unwrap()
+                    .channels_out
+                    .entry(uuid)
+                    .or_default()
+                    .extend(batch::decode(&buffer).map(Box::from));
+            }
+        }
+    });
+
+    link::<dyn FnMut(u64, u64, i32, u32) -> u32>(&host, "get_channel_events", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |uuid_0, uuid_1, ptr, len| -> u32 {
+            let uuid = Uuid::from_u64_pair(uuid_0, uuid_1);
+            let mut mod_state = mod_state.write().unwrap();
+            let Some(events_in) = mod_state.channels_in.get(&uuid) else { return 0 };
+            if events_in.is_empty() {
+                return 0;
+            }
+            let batch_len = batch::encoded_len(events_in.iter().map(|event| &event[..]));
+
+            // Tell the mod how much space it needs without giving up the events
+            if batch_len > len as usize {
+                return batch_len as u32;
+            }
+
+            let batch = batch::encode(events_in.iter().map(|event| &event[..]));
+            let arr = Uint8Array::from(&batch[..]);
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                Uint8Array::new(&memory.buffer()).set(&arr, ptr as u32);
+                mod_state.channels_in.remove(&uuid);
+                batch_len as u32
+            } else {
+                0
+            }
+        }
+    });
+
     link::<dyn FnMut() -> u64>(&host, "get_protocol_version", {
         move || -> u64 { protocol_version.to_u64() }
     });
@@ -128,26 +231,188 @@ pub fn build_linker(
         let memory = memory.clone();
         move |uuid_0, uuid_1, buffer_ptr, buffer_len| -> u32 {
             let uuid = Uuid::from_u64_pair(uuid_0, uuid_1);
-            let resource_bytes = mod_state
-                .write()
-                .unwrap()
-                .shared_resource_values
-                .remove(&uuid);
-            let Some(resource_bytes) = resource_bytes else { return 0 };
+            let mut mod_state = mod_state.write().unwrap();
+            if mod_state.denied_resources.contains(&uuid) {
+                return RESOURCE_DENIED;
+            }
+            let Some(resource_bytes) = mod_state.shared_resource_values.get(&uuid) else { return 0 };
+
+            // Tell the mod how much space it needs without marking the value as read
             if resource_bytes.len() > buffer_len as usize {
-                error!("Serialized event is too long");
-                return 0;
+                return resource_bytes.len() as u32;
             }
+
+            let len = resource_bytes.len() as u32;
             let arr = Uint8Array::from(&resource_bytes[..]);
             if let Some(memory) = memory.read().unwrap().as_ref() {
                 Uint8Array::new(&memory.buffer()).set(&arr, buffer_ptr as u32);
-                resource_bytes.len() as u32
+                mark_resource_read(&mut mod_state, uuid);
+                len
+            } else {
+                0
+            }
+        }
+    });
+
+    link::<dyn FnMut(u64, u64)>(&host, "request_resource_resync", {
+        let mod_state = mod_state.clone();
+        move |uuid_0, uuid_1| {
+            let uuid = Uuid::from_u64_pair(uuid_0, uuid_1);
+            request_resource_resync(&mut mod_state.write().unwrap(), uuid);
+        }
+    });
+
+    link::<dyn FnMut(u64, u64, i32, u32)>(&host, "store_resource", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |uuid_0, uuid_1, ptr, len| {
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                let buffer = Uint8Array::new(&memory.buffer())
+                    .slice(ptr as u32, ptr as u32 + len)
+                    .to_vec();
+                let uuid = Uuid::from_u64_pair(uuid_0, uuid_1);
+                store_resource_write(&mut mod_state.write().unwrap(), uuid, buffer.into());
+            }
+        }
+    });
+
+    link::<dyn FnMut(i32, u32)>(&host, "store_state", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |ptr, len| {
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                let buffer = Uint8Array::new(&memory.buffer())
+                    .slice(ptr as u32, ptr as u32 + len)
+                    .to_vec();
+                mod_state.write().unwrap().saved_state = Some(buffer.into());
+            }
+        }
+    });
+
+    link::<dyn FnMut(i32, u32) -> u32>(&host, "get_saved_state", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |buffer_ptr, buffer_len| -> u32 {
+            let mut mod_state = mod_state.write().unwrap();
+            let Some(saved_state) = mod_state.saved_state.as_ref() else { return 0 };
+
+            // Tell the mod how much space it needs without giving up the state
+            if saved_state.len() > buffer_len as usize {
+                return saved_state.len() as u32;
+            }
+
+            let arr = Uint8Array::from(&saved_state[..]);
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                Uint8Array::new(&memory.buffer()).set(&arr, buffer_ptr as u32);
+                mod_state
+                    .saved_state
+                    .take()
+                    .map_or(0, |state| state.len() as u32)
             } else {
                 0
             }
         }
     });
 
+    link::<dyn FnMut(i32, u32, i32, u32) -> u32>(&host, "call_host_function", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |name_ptr, name_len, args_ptr, args_len| -> u32 {
+            let mut mod_state = mod_state.write().unwrap();
+            mod_state.host_function_result = None;
+
+            let memory = memory.read().unwrap();
+            let Some(memory) = memory.as_ref() else { return HostCallStatus::Failed.to_u32() };
+            let buffer = Uint8Array::new(&memory.buffer());
+            let name = buffer
+                .slice(name_ptr as u32, name_ptr as u32 + name_len)
+                .to_vec();
+            let args = buffer
+                .slice(args_ptr as u32, args_ptr as u32 + args_len)
+                .to_vec();
+
+            let name = String::from_utf8_lossy(&name);
+            let Some(function) = host_functions.get(name.as_ref()) else {
+                error!("Mod called unknown host function {}", name);
+                return HostCallStatus::UnknownFunction.to_u32();
+            };
+
+            match function(codec, &args) {
+                Ok(result) => {
+                    mod_state.host_function_result = Some(result.into());
+                    HostCallStatus::Ok.to_u32()
+                }
+                Err(err) => {
+                    error!("Error in host function {}: {}", name, err);
+                    HostCallStatus::Failed.to_u32()
+                }
+            }
+        }
+    });
+
+    link::<dyn FnMut(i32, u32) -> u32>(&host, "get_host_function_result", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |buffer_ptr, buffer_len| -> u32 {
+            let mut mod_state = mod_state.write().unwrap();
+            let Some(result) = mod_state.host_function_result.as_ref() else { return 0 };
+
+            // Tell the mod how much space it needs without giving up the result
+            if result.len() > buffer_len as usize {
+                return result.len() as u32;
+            }
+
+            let arr = Uint8Array::from(&result[..]);
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                Uint8Array::new(&memory.buffer()).set(&arr, buffer_ptr as u32);
+                mod_state
+                    .host_function_result
+                    .take()
+                    .map_or(0, |result| result.len() as u32)
+            } else {
+                0
+            }
+        }
+    });
+
+    link::<dyn FnMut(i32, u32) -> u32>(&host, "get_call_args", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |buffer_ptr, buffer_len| -> u32 {
+            let mut mod_state = mod_state.write().unwrap();
+            let Some(args) = mod_state.call_args.as_ref() else { return 0 };
+
+            // Tell the mod how much space it needs without giving up the arguments
+            if args.len() > buffer_len as usize {
+                return args.len() as u32;
+            }
+
+            let arr = Uint8Array::from(&args[..]);
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                Uint8Array::new(&memory.buffer()).set(&arr, buffer_ptr as u32);
+                mod_state
+                    .call_args
+                    .take()
+                    .map_or(0, |args| args.len() as u32)
+            } else {
+                0
+            }
+        }
+    });
+
+    link::<dyn FnMut(i32, u32)>(&host, "store_call_result", {
+        let mod_state = mod_state.clone();
+        let memory = memory.clone();
+        move |ptr, len| {
+            if let Some(memory) = memory.read().unwrap().as_ref() {
+                let buffer = Uint8Array::new(&memory.buffer())
+                    .slice(ptr as u32, ptr as u32 + len)
+                    .to_vec();
+                mod_state.write().unwrap().call_result = Some(buffer.into());
+            }
+        }
+    });
+
     // __wbindgen_placeholder__
     let wbp = Object::new();
 
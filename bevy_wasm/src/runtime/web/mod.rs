@@ -1,12 +1,14 @@
 use std::{
     collections::VecDeque,
+    path::PathBuf,
     sync::{Arc, RwLock},
+    task::Poll,
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bevy::{
-    prelude::{Component, Resource},
-    utils::{HashMap, Instant},
+    prelude::{Component, Entity, Handle, Resource},
+    utils::{HashMap, HashSet, Instant, Uuid},
 };
 use js_sys::{
     Function, Reflect,
@@ -14,10 +16,23 @@ use js_sys::{
 };
 use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
 
-use bevy_wasm_shared::version::Version;
-use web_sys::console;
+use bevy_wasm_shared::{codec::Codec, version::Version};
+use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{mod_state::ModState, SharedResource};
+use crate::{
+    components::WasmMod,
+    error::{CallError, ModError},
+    limits::ModLimits,
+    mod_state::ModState,
+    shared_resources::ResourceAccess,
+    wasm_asset::WasmAsset,
+    SharedResource,
+};
+
+use super::{
+    check_codec, check_ffi_version, check_protocol_version, denied_resources, deny_resource,
+    next_instance_id, request_resource_resync, HostFunctions, VersionChecker,
+};
 
 use self::linker::build_linker;
 
@@ -26,63 +41,233 @@ mod linker;
 #[derive(Resource)]
 pub struct WasmRuntime {
     protocol_version: Version,
+    version_checker: VersionChecker,
+    /// Whether mods that don't export their protocol version are loaded anyway
+    allow_unversioned_mods: bool,
+    codec: Codec,
+    host_functions: HostFunctions,
+    /// The latest value of every shared resource, to seed new instances with
+    shared_resource_values: HashMap<Uuid, Arc<[u8]>>,
+    /// Which mods may read each restricted shared resource
+    resource_access: HashMap<Uuid, ResourceAccess>,
+    /// Shared resources mods may write to
+    writable_resources: HashSet<Uuid>,
 }
 
 impl WasmRuntime {
-    pub fn new(protocol_version: Version) -> Self {
-        Self { protocol_version }
+    /// Create the runtime. Resource limits are not enforced in the browser,
+    /// and the browser caches compiled modules itself.
+    pub fn new(
+        protocol_version: Version,
+        _limits: ModLimits,
+        version_checker: VersionChecker,
+        _module_cache: Option<PathBuf>,
+        codec: Codec,
+        host_functions: HostFunctions,
+        resource_access: HashMap<Uuid, ResourceAccess>,
+    ) -> Self {
+        Self {
+            protocol_version,
+            version_checker,
+            allow_unversioned_mods: false,
+            codec,
+            host_functions,
+            shared_resource_values: HashMap::new(),
+            resource_access,
+            writable_resources: HashSet::new(),
+        }
     }
 
-    pub fn create_instance(&self, wasm_bytes: &[u8]) -> Result<WasmInstance> {
+    /// Compiled modules are not shared in the browser, so there is nothing to forget
+    pub fn forget_module(&mut self, _handle: &Handle<WasmAsset>) {}
+
+    /// Remember the latest value of a shared resource, so new instances start with it
+    pub fn set_shared_resource_value<T: SharedResource>(&mut self, bytes: Arc<[u8]>) {
+        self.shared_resource_values.insert(T::TYPE_UUID, bytes);
+    }
+
+    /// Forget the value of a shared resource the game removed, so new instances start without it
+    pub fn remove_shared_resource_value<T: SharedResource>(&mut self) {
+        self.shared_resource_values.remove(&T::TYPE_UUID);
+    }
+
+    /// Load mods that don't export their protocol version, assuming they are compatible
+    pub fn allow_unversioned_mods(&mut self) {
+        self.allow_unversioned_mods = true;
+    }
+
+    /// Let mods write to the shared resource `T`. Writes to other resources are dropped.
+    pub fn allow_resource_writes<T: SharedResource>(&mut self) {
+        self.writable_resources.insert(T::TYPE_UUID);
+    }
+
+    /// Whether the mod may read the shared resource `T`
+    pub fn may_read_resource<T: SharedResource>(&self, entity: Entity, wasm_mod: &WasmMod) -> bool {
+        self.resource_access
+            .get(&T::TYPE_UUID)
+            .is_none_or(|access| access.allows(entity, wasm_mod))
+    }
+
+    /// The latest value of every shared resource the mod may read, and the resources it may not
+    fn seed_resources(
+        &self,
+        entity: Entity,
+        wasm_mod: &WasmMod,
+    ) -> (HashMap<Uuid, Arc<[u8]>>, HashSet<Uuid>) {
+        let denied = denied_resources(&self.resource_access, entity, wasm_mod);
+        let values = self
+            .shared_resource_values
+            .iter()
+            .filter(|(uuid, _)| !denied.contains(*uuid))
+            .map(|(uuid, bytes)| (*uuid, bytes.clone()))
+            .collect();
+        (values, denied)
+    }
+
+    /// Create a new mod instance. Deadlines are not enforced in the browser.
+    pub fn create_instance(
+        &mut self,
+        entity: Entity,
+        wasm_mod: &WasmMod,
+        wasm_bytes: &[u8],
+    ) -> Result<WasmInstance> {
+        let (shared_resource_values, denied_resources) = self.seed_resources(entity, wasm_mod);
         let memory = Arc::new(RwLock::new(None));
         let mod_state = Arc::new(RwLock::new(ModState {
+            entity,
+            instance_id: next_instance_id(),
+            codec: self.codec,
             startup_time: Instant::now(),
             app_ptr: 0,
             events_in: VecDeque::new(),
             events_out: Vec::new(),
-            shared_resource_values: HashMap::new(),
+            channels_in: HashMap::new(),
+            channels_out: HashMap::new(),
+            shared_resource_values,
+            resource_versions: HashMap::new(),
+            resource_resyncs: HashSet::new(),
+            denied_resources,
+            writable_resources: self.writable_resources.clone(),
+            resource_writes: HashMap::new(),
+            host_function_result: None,
+            call_args: None,
+            call_result: None,
+            saved_state: None,
         }));
-        let imports = build_linker(self.protocol_version, mod_state.clone(), memory.clone());
+        let imports = build_linker(
+            self.protocol_version,
+            self.codec,
+            self.host_functions.clone(),
+            mod_state.clone(),
+            memory.clone(),
+        );
         let promise = WebAssembly::instantiate_buffer(wasm_bytes, &imports);
         let instance = Arc::new(RwLock::new(None));
+        let loaded = Arc::new(RwLock::new(None));
         let then = Closure::new({
             let instance = instance.clone();
+            let loaded = loaded.clone();
+            let mod_state = mod_state.clone();
+            let protocol_version = self.protocol_version;
+            let codec = self.codec;
+            let version_checker = self.version_checker.clone();
+            let allow_unversioned_mods = self.allow_unversioned_mods;
             move |value| {
-                let instance_value: WebAssembly::Instance =
-                    Reflect::get(&value, &"instance".into())
+                let result = (|| -> Result<()> {
+                    let instance_value: WebAssembly::Instance =
+                        Reflect::get(&value, &"instance".into())
+                            .and_then(|x| x.dyn_into())
+                            .map_err(js_error)?;
+                    let exports = instance_value.exports();
+                    let memory_value: WebAssembly::Memory =
+                        Reflect::get(&exports, &"memory".into())
+                            .and_then(|x| x.dyn_into())
+                            .map_err(js_error)?;
+
+                    // Call `get_mod_ffi_version` and `get_mod_protocol_version` before letting
+                    // the mod build its app
+                    let get_mod_ffi_version =
+                        Reflect::get(exports.as_ref(), &"get_mod_ffi_version".into())
+                            .map_err(js_error)?;
+                    let mod_ffi_version = match get_mod_ffi_version.dyn_into::<Function>() {
+                        Ok(func) => {
+                            let version = func.call0(&JsValue::undefined()).map_err(js_error)?;
+                            version.as_f64().map(|version| version as u32)
+                        }
+                        Err(_) => None,
+                    };
+                    check_ffi_version(entity, mod_ffi_version)?;
+
+                    let get_mod_protocol_version =
+                        Reflect::get(exports.as_ref(), &"get_mod_protocol_version".into())
+                            .map_err(js_error)?;
+                    let mod_version = match get_mod_protocol_version.dyn_into::<Function>() {
+                        Ok(func) => {
+                            let version = func.call0(&JsValue::undefined()).map_err(js_error)?;
+                            let version = u64::try_from(version).map_err(js_error)?;
+                            Some(Version::from_u64(version))
+                        }
+                        Err(_) => None,
+                    };
+                    check_protocol_version(
+                        &version_checker,
+                        allow_unversioned_mods,
+                        entity,
+                        protocol_version,
+                        mod_version,
+                    )?;
+
+                    let build_app: Function = Reflect::get(exports.as_ref(), &"build_app".into())
                         .and_then(|x| x.dyn_into())
-                        .unwrap();
-                let exports = instance_value.exports();
-                let memory_value: WebAssembly::Memory = Reflect::get(&exports, &"memory".into())
-                    .and_then(|x| x.dyn_into())
-                    .unwrap();
-                let build_app: Function = Reflect::get(exports.as_ref(), &"build_app".into())
-                    .and_then(|x| x.dyn_into())
-                    .expect("build_app export wasn't a function");
-                *instance.write().unwrap() = Some(instance_value);
-                *memory.write().unwrap() = Some(memory_value);
-                build_app.call0(&JsValue::undefined()).unwrap();
+                        .map_err(js_error)?;
+                    *instance.write().unwrap() = Some(instance_value);
+                    *memory.write().unwrap() = Some(memory_value);
+                    build_app.call0(&JsValue::undefined()).map_err(js_error)?;
+                    if mod_state.read().unwrap().app_ptr == 0 {
+                        return Err(ModError::AppNotBuilt { entity }.into());
+                    }
+
+                    // Call `get_mod_codec` now that the mod set it
+                    let get_mod_codec = Reflect::get(exports.as_ref(), &"get_mod_codec".into())
+                        .map_err(js_error)?;
+                    let mod_codec = match get_mod_codec.dyn_into::<Function>() {
+                        Ok(func) => {
+                            let codec = func.call0(&JsValue::undefined()).map_err(js_error)?;
+                            codec.as_f64().map(|codec| codec as u32)
+                        }
+                        Err(_) => None,
+                    };
+                    check_codec(entity, codec, mod_codec)?;
+                    Ok(())
+                })();
+                *loaded.write().unwrap() = Some(result);
             }
         });
         let catch = Closure::new({
+            let loaded = loaded.clone();
             move |value| {
-                console::warn_1(&value);
+                *loaded.write().unwrap() = Some(Err(js_error(value)));
             }
         });
         _ = promise.then(&then).catch(&catch);
         Ok(WasmInstance {
             instance,
             mod_state,
+            loaded,
             _then: then,
             _catch: catch,
         })
     }
 }
 
+/// A running instance of a [`WasmMod`], added to its entity as soon as the mod starts loading
+///
+/// Use it to call the functions the mod exported, see [`WasmInstance::call`].
 #[derive(Component)]
 pub struct WasmInstance {
     instance: Arc<RwLock<Option<Instance>>>,
     mod_state: Arc<RwLock<ModState>>,
+    loaded: Arc<RwLock<Option<Result<()>>>>,
     _then: Closure<dyn FnMut(JsValue)>,
     _catch: Closure<dyn FnMut(JsValue)>,
 }
@@ -91,8 +276,27 @@ unsafe impl Send for WasmInstance {}
 unsafe impl Sync for WasmInstance {}
 
 impl WasmInstance {
-    pub fn tick(&mut self, events_in: &[Arc<[u8]>]) -> Result<Vec<Box<[u8]>>> {
-        let Some(instance) = self.instance.read().unwrap().clone() else { return Ok(Vec::new()) };
+    /// Whether the instance has been created and `build_app` has finished
+    ///
+    /// Returns [`Poll::Ready`] only once.
+    pub(crate) fn poll_loaded(&self) -> Poll<Result<()>> {
+        match self.loaded.write().unwrap().take() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Tick the internal mod state
+    ///
+    /// Fuel budgets and deadlines are not enforced in the browser.
+    pub(crate) fn tick(
+        &mut self,
+        _wasm_mod: &WasmMod,
+        events_in: &[Arc<[u8]>],
+    ) -> Result<Vec<Box<[u8]>>> {
+        let Some(instance) = self.instance.read().unwrap().clone() else {
+            return Ok(Vec::new());
+        };
         for event in events_in.iter() {
             self.mod_state
                 .write()
@@ -108,21 +312,179 @@ impl WasmInstance {
         let update: Function = Reflect::get(exports.as_ref(), &"update".into())
             .and_then(|x| x.dyn_into())
             .expect("build_app export wasn't a function");
-        match update.call1(&JsValue::undefined(), &JsValue::from_f64(app_ptr as f64)) {
-            Ok(_) => {}
-            Err(e) => console::error_1(&e),
+        if let Err(err) = update.call1(&JsValue::undefined(), &JsValue::from_f64(app_ptr as f64)) {
+            // Whatever the mod sent before it failed is thrown away, and so is what it didn't
+            // get to read
+            let mut mod_state = self.mod_state.write().unwrap();
+            mod_state.events_out.clear();
+            mod_state.channels_out.clear();
+            mod_state.resource_writes.clear();
+            mod_state.events_in.clear();
+            mod_state.channels_in.clear();
+            return Err(js_error(err).context("Failed to call update"));
         }
 
+        // Mods that don't use a channel never read it, so don't keep its messages around
+        self.mod_state.write().unwrap().channels_in.clear();
+
         let serialized_events_out = std::mem::take(&mut self.mod_state.write().unwrap().events_out);
 
         Ok(serialized_events_out)
     }
 
-    pub fn update_resource_value<T: SharedResource>(&mut self, bytes: Arc<[u8]>) {
+    /// Unique to this instance of the mod, unlike its entity
+    pub(crate) fn id(&self) -> u64 {
+        self.mod_state.read().unwrap().instance_id
+    }
+
+    /// Queue events on an additional channel for the mod's next tick. The mod must read them
+    /// during that tick, they are dropped after it.
+    pub(crate) fn push_channel_events(&mut self, channel: Uuid, events: &[Arc<[u8]>]) {
+        let mut mod_state = self.mod_state.write().unwrap();
+        let channel = mod_state.channels_in.entry(channel).or_default();
+        channel.extend(events.iter().cloned());
+    }
+
+    /// Take the events the mod sent on an additional channel
+    pub(crate) fn take_channel_events(&mut self, channel: Uuid) -> Vec<Box<[u8]>> {
+        self.mod_state
+            .write()
+            .unwrap()
+            .channels_out
+            .remove(&channel)
+            .unwrap_or_default()
+    }
+
+    /// Call a function the mod exported with `#[bevy_wasm_sys::export]`, and return its result
+    ///
+    /// Arguments and results are serialized with the plugin's codec. A mod that fails during a call
+    /// is not marked as [`Failed`](crate::components::WasmModStatus::Failed), that is up to the caller.
+    pub fn call<Args, Ret>(&mut self, name: &str, args: &Args) -> Result<Ret, CallError>
+    where
+        Args: Serialize + ?Sized,
+        Ret: DeserializeOwned,
+    {
+        if self.instance.read().unwrap().is_none() {
+            return Err(CallError::NotLoaded);
+        }
+        let export = self
+            .get_export(name)
+            .ok_or_else(|| CallError::MissingExport(name.to_string()))?;
+
+        let codec = self.mod_state.read().unwrap().codec;
+        let args = codec.encode(args)?;
+
+        self.mod_state.write().unwrap().call_args = Some(args.into());
+        let result = export.call0(&JsValue::undefined());
+        let call_result = {
+            let mut mod_state = self.mod_state.write().unwrap();
+            mod_state.call_args = None;
+            mod_state.call_result.take()
+        };
+
+        result.map_err(|err| CallError::Failed(js_error(err)))?;
+        let call_result = call_result.ok_or(CallError::NoResult)?;
+
+        Ok(codec.decode(&call_result)?)
+    }
+
+    /// Call the mod's `save_state` export, if it has one, and return what it saved
+    pub(crate) fn save_state(&mut self, _wasm_mod: &WasmMod) -> Result<Option<Box<[u8]>>> {
+        let Some(save_state) = self.get_export("save_state") else {
+            return Ok(None);
+        };
+
+        let app_ptr = self.mod_state.read().unwrap().app_ptr;
+        save_state
+            .call1(&JsValue::undefined(), &JsValue::from_f64(app_ptr as f64))
+            .map_err(js_error)?;
+
+        Ok(self.mod_state.write().unwrap().saved_state.take())
+    }
+
+    /// Hand state saved by a previous instance to the mod's `restore_state` export, if it has one
+    pub(crate) fn restore_state(&mut self, _wasm_mod: &WasmMod, state: Box<[u8]>) -> Result<()> {
+        let Some(restore_state) = self.get_export("restore_state") else {
+            return Ok(());
+        };
+
+        let app_ptr = {
+            let mut mod_state = self.mod_state.write().unwrap();
+            mod_state.saved_state = Some(state);
+            mod_state.app_ptr
+        };
+        let result = restore_state.call1(&JsValue::undefined(), &JsValue::from_f64(app_ptr as f64));
+        self.mod_state.write().unwrap().saved_state = None;
+
+        result.map(|_| ()).map_err(js_error)
+    }
+
+    /// Get an exported function, if the instance is loaded and has it
+    fn get_export(&self, name: &str) -> Option<Function> {
+        let instance = self.instance.read().unwrap().clone()?;
+        Reflect::get(instance.exports().as_ref(), &name.into())
+            .and_then(|x| x.dyn_into())
+            .ok()
+    }
+
+    /// Update the value of a shared resource as seen by the mod, with a serialized `ResourceUpdate`
+    pub(crate) fn update_resource_value<T: SharedResource>(&mut self, bytes: Arc<[u8]>) {
         self.mod_state
             .write()
             .unwrap()
             .shared_resource_values
             .insert(T::TYPE_UUID, bytes);
     }
+
+    /// Version of the last value of a shared resource the mod read
+    pub(crate) fn resource_version<T: SharedResource>(&self) -> Option<u64> {
+        self.mod_state
+            .read()
+            .unwrap()
+            .resource_versions
+            .get(&T::TYPE_UUID)
+            .copied()
+    }
+
+    /// Send the whole value of a shared resource to the mod on its next update
+    pub(crate) fn request_resource_resync<T: SharedResource>(&mut self) {
+        request_resource_resync(&mut self.mod_state.write().unwrap(), T::TYPE_UUID);
+    }
+
+    /// Whether the mod needs the whole value of a shared resource, even if it didn't change
+    pub(crate) fn take_resource_resync<T: SharedResource>(&mut self) -> bool {
+        self.mod_state
+            .write()
+            .unwrap()
+            .resource_resyncs
+            .remove(&T::TYPE_UUID)
+    }
+
+    /// Stop sending a shared resource to the mod, and report its reads as denied
+    pub(crate) fn deny_resource<T: SharedResource>(&mut self) {
+        deny_resource(&mut self.mod_state.write().unwrap(), T::TYPE_UUID);
+    }
+
+    /// Let the mod read a shared resource again. Returns whether it was denied until now.
+    pub(crate) fn allow_resource<T: SharedResource>(&mut self) -> bool {
+        self.mod_state
+            .write()
+            .unwrap()
+            .denied_resources
+            .remove(&T::TYPE_UUID)
+    }
+
+    /// Take the value the mod wrote to a shared resource since it was last taken
+    pub(crate) fn take_resource_write<T: SharedResource>(&mut self) -> Option<Box<[u8]>> {
+        self.mod_state
+            .write()
+            .unwrap()
+            .resource_writes
+            .remove(&T::TYPE_UUID)
+    }
+}
+
+/// JavaScript errors can't be sent between threads, so only keep their description
+fn js_error(err: JsValue) -> anyhow::Error {
+    anyhow!("{:?}", err)
 }
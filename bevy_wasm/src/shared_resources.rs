@@ -0,0 +1,105 @@
+//! Options for resources shared with mods
+
+use std::{
+    fmt::{self, Debug},
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use bevy::prelude::{Entity, Resource};
+
+use crate::components::WasmMod;
+
+/// What the game does when mods write to a resource shared with
+/// [`WasmPlugin::share_resource_mut`](crate::plugin::WasmPlugin::share_resource_mut)
+///
+/// Writes are applied after the mods are ticked, in the order of the mods' entities.
+/// Mods whose writes are dropped get the game's value back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Apply every write. The last mod to write wins, even over changes the game made this frame.
+    #[default]
+    LastWriterWins,
+
+    /// Drop the mods' writes if the game changed the resource this frame
+    HostWins,
+
+    /// Drop every write if the game changed the resource this frame, or if more than one mod wrote to it
+    Reject,
+}
+
+/// The conflict policy of a resource mods may write to
+#[derive(Resource)]
+pub(crate) struct WritableResource<T> {
+    pub policy: ConflictPolicy,
+    _t: PhantomData<fn() -> T>,
+}
+
+impl<T> WritableResource<T> {
+    pub fn new(policy: ConflictPolicy) -> Self {
+        Self {
+            policy,
+            _t: PhantomData,
+        }
+    }
+}
+
+/// Marks a resource whose changes are sent to mods as patches, see
+/// [`WasmPlugin::with_resource_deltas`](crate::plugin::WasmPlugin::with_resource_deltas)
+#[derive(Resource)]
+pub(crate) struct DeltaEncoded<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for DeltaEncoded<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// Decides whether a mod may read a resource, given its entity and [`WasmMod`]
+pub type AccessCheck = Arc<dyn Fn(Entity, &WasmMod) -> bool + Send + Sync>;
+
+/// Which mods may read a shared resource, set with
+/// [`WasmPlugin::with_resource_access`](crate::plugin::WasmPlugin::with_resource_access)
+///
+/// Checked every frame, so changing a mod's [`WasmMod::capabilities`] takes effect on its next tick.
+/// Mods that may not read a resource get an error when they try, and their writes to it are dropped.
+#[derive(Clone, Default)]
+pub enum ResourceAccess {
+    /// Every mod
+    #[default]
+    Everyone,
+
+    /// Mods given this capability with [`WasmMod::with_capability`]
+    Capability(String),
+
+    /// Mods the function returns `true` for, given their entity and [`WasmMod`]
+    Custom(AccessCheck),
+}
+
+impl ResourceAccess {
+    /// Let the mods `allows` returns `true` for read the resource
+    pub fn custom(allows: impl Fn(Entity, &WasmMod) -> bool + Send + Sync + 'static) -> Self {
+        ResourceAccess::Custom(Arc::new(allows))
+    }
+
+    /// Whether the mod may read the resource
+    pub fn allows(&self, entity: Entity, wasm_mod: &WasmMod) -> bool {
+        match self {
+            ResourceAccess::Everyone => true,
+            ResourceAccess::Capability(capability) => wasm_mod.capabilities.contains(capability),
+            ResourceAccess::Custom(allows) => allows(entity, wasm_mod),
+        }
+    }
+}
+
+impl Debug for ResourceAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceAccess::Everyone => write!(f, "Everyone"),
+            ResourceAccess::Capability(capability) => {
+                f.debug_tuple("Capability").field(capability).finish()
+            }
+            ResourceAccess::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
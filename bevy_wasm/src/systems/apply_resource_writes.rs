@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+
+use crate::{
+    runtime::WasmInstance,
+    shared_resources::{ConflictPolicy, WritableResource},
+    SharedResource,
+};
+
+use super::ProtocolCodec;
+
+/// Apply the values mods wrote to a shared resource during their tick, or since the last one
+pub fn apply_resource_writes<T: SharedResource>(
+    res: Option<ResMut<T>>,
+    writable: Res<WritableResource<T>>,
+    codec: Res<ProtocolCodec>,
+    mut wasm_mods: Query<(Entity, &mut WasmInstance)>,
+) {
+    let mut wasm_mods: Vec<_> = wasm_mods.iter_mut().collect();
+    wasm_mods.sort_by_key(|(entity, _)| *entity);
+
+    let mut writers = Vec::new();
+    let mut values = Vec::new();
+    for (entity, wasm_instance) in wasm_mods.iter_mut() {
+        let Some(bytes) = wasm_instance.take_resource_write::<T>() else {
+            continue;
+        };
+        writers.push(*entity);
+        match codec.0.decode::<T>(&bytes) {
+            Ok(value) => values.push(value),
+            Err(err) => error!("Error while deserializing resource: {}", err),
+        }
+    }
+
+    if writers.is_empty() {
+        return;
+    }
+
+    // The writers hear about the removal on their next update
+    let Some(mut res) = res else {
+        warn!(
+            "Dropped writes to {} by mods {:?}, which the game removed",
+            std::any::type_name::<T>(),
+            writers
+        );
+        return;
+    };
+
+    let conflict = match writable.policy {
+        ConflictPolicy::LastWriterWins => false,
+        ConflictPolicy::HostWins => res.is_changed(),
+        ConflictPolicy::Reject => res.is_changed() || writers.len() > 1,
+    };
+
+    // Writes are applied in the order of the mods' entities, so the last one wins
+    if !conflict {
+        if let Some(value) = values.pop() {
+            *res = value;
+            return;
+        }
+    }
+
+    // The writers would keep their own value otherwise
+    warn!(
+        "Dropped writes to {} by mods {:?}",
+        std::any::type_name::<T>(),
+        writers
+    );
+    for (entity, wasm_instance) in wasm_mods.iter_mut() {
+        if writers.contains(entity) {
+            wasm_instance.request_resource_resync::<T>();
+        }
+    }
+}
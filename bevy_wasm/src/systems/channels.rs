@@ -0,0 +1,39 @@
+use bevy::{prelude::*, reflect::TypeUuid};
+
+use crate::{components::WasmModStatus, runtime::WasmInstance, Message};
+
+use super::{
+    messages::{MessagesIn, MessagesOut},
+    ProtocolCodec,
+};
+
+/// Queue this frame's messages on an additional channel, before the mods are ticked
+pub fn send_channel_messages<In: Message + TypeUuid>(
+    codec: Res<ProtocolCodec>,
+    mut events_in: MessagesIn<In>,
+    mut wasm_mods: Query<(Entity, &WasmModStatus, &mut WasmInstance)>,
+) {
+    let events_in = events_in.serialize(codec.0);
+
+    for (entity, status, mut wasm_instance) in wasm_mods.iter_mut() {
+        if *status != WasmModStatus::Running {
+            continue;
+        }
+        wasm_instance.push_channel_events(In::TYPE_UUID, &events_in.for_mod(entity));
+    }
+}
+
+/// Send the messages mods sent on an additional channel, after they were ticked
+pub fn receive_channel_messages<Out: Message + TypeUuid>(
+    codec: Res<ProtocolCodec>,
+    mut events_out: MessagesOut<Out>,
+    mut wasm_mods: Query<(Entity, &mut WasmInstance)>,
+) {
+    let mut wasm_mods: Vec<_> = wasm_mods.iter_mut().collect();
+    wasm_mods.sort_by_key(|(entity, _)| *entity);
+
+    for (entity, mut wasm_instance) in wasm_mods {
+        let serialized_events_out = wasm_instance.take_channel_events(Out::TYPE_UUID);
+        events_out.send_serialized(codec.0, entity, serialized_events_out);
+    }
+}
@@ -1,29 +1,161 @@
-use bevy::prelude::*;
+use std::task::Poll;
+
+use bevy::{asset::LoadState, ecs::system::SystemParam, prelude::*};
 
 use crate::{
-    components::WasmMod,
+    components::{WasmMod, WasmModStatus},
+    error::ModError,
+    events::{ModFailed, ModLoaded, ModReloaded},
     runtime::{WasmInstance, WasmRuntime},
     wasm_asset::WasmAsset,
 };
 
+use super::reload_instances::Reloading;
+
+#[derive(SystemParam)]
+pub struct LifecycleEvents<'w> {
+    loaded: EventWriter<'w, ModLoaded>,
+    reloaded: EventWriter<'w, ModReloaded>,
+    failed: EventWriter<'w, ModFailed>,
+}
+
+type ModToLoad<'a> = (
+    Entity,
+    &'a WasmMod,
+    Option<&'a WasmModStatus>,
+    Option<&'a Reloading>,
+);
+
 pub fn load_instances(
     mut commands: Commands,
+    asset_server: Res<AssetServer>,
     wasm_assets: Res<Assets<WasmAsset>>,
-    mods_to_load: Query<(Entity, &WasmMod), Without<WasmInstance>>,
-    wasm_runtime: Res<WasmRuntime>,
+    mods_to_load: Query<ModToLoad, Without<WasmInstance>>,
+    mut pending_instances: Query<(
+        Entity,
+        &WasmMod,
+        &WasmModStatus,
+        &mut WasmInstance,
+        Option<&Reloading>,
+    )>,
+    mut wasm_runtime: ResMut<WasmRuntime>,
+    mut events: LifecycleEvents,
 ) {
-    for (entity, mod_to_load) in mods_to_load.iter() {
-        if let Some(wasm_asset) = wasm_assets.get(&mod_to_load.wasm) {
-            let instance = wasm_runtime.create_instance(&wasm_asset.bytes);
-            match instance {
-                Ok(instance) => {
-                    commands.entity(entity).insert(instance);
-                }
-                Err(e) => {
-                    error!("Could not initialize WASM instance: {}", e);
-                    commands.entity(entity).despawn();
+    for (entity, mod_to_load, status, reloading) in mods_to_load.iter() {
+        match status {
+            None | Some(WasmModStatus::Unloaded) => {
+                commands.entity(entity).insert(WasmModStatus::Loading);
+            }
+            Some(WasmModStatus::Loading) => {}
+            Some(_) => continue,
+        }
+
+        let Some(wasm_asset) = wasm_assets.get(&mod_to_load.wasm) else {
+            if asset_server.get_load_state(&mod_to_load.wasm) == LoadState::Failed {
+                let reason = "Could not load the WASM asset".to_string();
+                error!("Mod {:?}: {}", entity, reason);
+                commands
+                    .entity(entity)
+                    .insert(WasmModStatus::Failed(reason.clone()));
+                events.failed.send(ModFailed { entity, reason });
+            }
+            continue;
+        };
+
+        let result = match wasm_runtime.create_instance(entity, mod_to_load, &wasm_asset.bytes) {
+            Ok(mut instance) => {
+                let result = instance.poll_loaded();
+                if let Poll::Ready(Ok(())) = result {
+                    restore_saved_state(entity, mod_to_load, &mut instance, reloading);
                 }
+                commands.entity(entity).insert(instance);
+                result
+            }
+            Err(err) => Poll::Ready(Err(err)),
+        };
+
+        if let Poll::Ready(result) = result {
+            finish_loading(
+                &mut commands,
+                &mut events,
+                entity,
+                reloading.is_some(),
+                result,
+            );
+        }
+    }
+
+    // Instances that are instantiated asynchronously
+    for (entity, wasm_mod, status, mut instance, reloading) in pending_instances.iter_mut() {
+        if *status != WasmModStatus::Loading {
+            continue;
+        }
+        if let Poll::Ready(result) = instance.poll_loaded() {
+            if result.is_ok() {
+                restore_saved_state(entity, wasm_mod, &mut instance, reloading);
             }
+            finish_loading(
+                &mut commands,
+                &mut events,
+                entity,
+                reloading.is_some(),
+                result,
+            );
+        }
+    }
+}
+
+/// Hand the state saved by the old instance of a reloaded mod to the new one
+fn restore_saved_state(
+    entity: Entity,
+    wasm_mod: &WasmMod,
+    instance: &mut WasmInstance,
+    reloading: Option<&Reloading>,
+) {
+    let Some(saved_state) = reloading.and_then(|reloading| reloading.saved_state.clone()) else {
+        return;
+    };
+
+    if let Err(err) = instance.restore_state(wasm_mod, saved_state) {
+        warn!("Could not restore the state of mod {:?}: {:#}", entity, err);
+    }
+}
+
+fn finish_loading(
+    commands: &mut Commands,
+    events: &mut LifecycleEvents,
+    entity: Entity,
+    reloading: bool,
+    result: anyhow::Result<()>,
+) {
+    commands.entity(entity).remove::<Reloading>();
+
+    match result {
+        Ok(()) => {
+            commands.entity(entity).insert(WasmModStatus::Running);
+            if reloading {
+                events.reloaded.send(ModReloaded { entity });
+            } else {
+                events.loaded.send(ModLoaded { entity });
+            }
+        }
+        Err(err) => {
+            let reason = format!("{:#}", err);
+            error!("Could not initialize WASM instance: {}", reason);
+            let status = match err.downcast_ref::<ModError>() {
+                Some(
+                    ModError::IncompatibleVersion { .. }
+                    | ModError::IncompatibleFfi { .. }
+                    | ModError::IncompatibleCodec { .. }
+                    | ModError::AppNotBuilt { .. },
+                ) => WasmModStatus::Incompatible,
+                _ => WasmModStatus::Failed(reason.clone()),
+            };
+            commands
+                .entity(entity)
+                .remove::<WasmInstance>()
+                .insert(status);
+            events.failed.send(ModFailed { entity, reason });
         }
     }
 }
@@ -0,0 +1,89 @@
+use std::{borrow::Cow, sync::Arc};
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use bevy_wasm_shared::codec::Codec;
+
+use crate::{
+    events::{FromMod, ToMod},
+    Message,
+};
+
+use super::TagMessages;
+
+/// The messages for mods: broadcast to all of them, or sent to a single one
+#[derive(SystemParam)]
+pub struct MessagesIn<'w, 's, In: Message> {
+    broadcast: EventReader<'w, 's, In>,
+    targeted: EventReader<'w, 's, ToMod<In>>,
+}
+
+impl<'w, 's, In: Message> MessagesIn<'w, 's, In> {
+    /// Serialize this frame's messages
+    pub fn serialize(&mut self, codec: Codec) -> SerializedMessages {
+        let broadcast: Vec<Arc<[u8]>> = self
+            .broadcast
+            .iter()
+            .flat_map(|event| codec.encode(event))
+            .map(|bytes| bytes.into())
+            .collect();
+
+        let mut targeted: HashMap<Entity, Vec<Arc<[u8]>>> = HashMap::new();
+        for ToMod { target, message } in self.targeted.iter() {
+            match codec.encode(message) {
+                Ok(bytes) => targeted.entry(*target).or_default().push(bytes.into()),
+                Err(err) => error!("Error while serializing event: {}", err),
+            }
+        }
+
+        SerializedMessages {
+            broadcast,
+            targeted,
+        }
+    }
+}
+
+/// A frame's messages for mods, serialized once and shared between them
+pub struct SerializedMessages {
+    broadcast: Vec<Arc<[u8]>>,
+    targeted: HashMap<Entity, Vec<Arc<[u8]>>>,
+}
+
+impl SerializedMessages {
+    /// Broadcast messages, followed by the ones sent to this mod only
+    pub fn for_mod(&self, entity: Entity) -> Cow<'_, [Arc<[u8]>]> {
+        match self.targeted.get(&entity) {
+            Some(targeted) => [self.broadcast.as_slice(), targeted.as_slice()]
+                .concat()
+                .into(),
+            None => self.broadcast.as_slice().into(),
+        }
+    }
+}
+
+/// Where the messages sent by mods go: as plain events, or tagged with their source
+#[derive(SystemParam)]
+pub struct MessagesOut<'w, Out: Message> {
+    tag: Res<'w, TagMessages>,
+    plain: EventWriter<'w, Out>,
+    tagged: EventWriter<'w, FromMod<Out>>,
+}
+
+impl<'w, Out: Message> MessagesOut<'w, Out> {
+    /// Deserialize and send the messages a mod sent during its tick
+    pub fn send_serialized(&mut self, codec: Codec, source: Entity, messages: Vec<Box<[u8]>>) {
+        for message in messages {
+            match codec.decode::<Out>(&message) {
+                Ok(message) => self.send(source, message),
+                Err(err) => error!("Error while deserializing event: {}", err),
+            }
+        }
+    }
+
+    fn send(&mut self, source: Entity, message: Out) {
+        if self.tag.0 {
+            self.tagged.send(FromMod { source, message });
+        } else {
+            self.plain.send(message);
+        }
+    }
+}
@@ -1,7 +1,30 @@
+use bevy::prelude::Resource;
+use bevy_wasm_shared::codec::Codec;
+
+pub use apply_resource_writes::apply_resource_writes;
+pub use channels::{receive_channel_messages, send_channel_messages};
 pub use load_instances::load_instances;
-pub use tick_mods::tick_mods;
+pub use reload_instances::reload_instances;
+pub use rpc::{receive_mod_requests, send_mod_responses};
+pub use tick_mods::{tick_mods, TickSettings};
+pub use unload_instances::unload_instances;
 pub use update_shared_resource::update_shared_resource;
 
+mod apply_resource_writes;
+mod channels;
 mod load_instances;
+mod messages;
+mod reload_instances;
+mod rpc;
 mod tick_mods;
+mod unload_instances;
 mod update_shared_resource;
+
+/// The codec messages and shared resources are serialized with
+#[derive(Resource, Clone, Copy)]
+pub struct ProtocolCodec(pub Codec);
+
+/// Whether messages from mods are sent as [`FromMod`](crate::events::FromMod) events
+/// instead of plain ones
+#[derive(Resource, Clone, Copy)]
+pub struct TagMessages(pub bool);
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{WasmMod, WasmModStatus},
+    runtime::{WasmInstance, WasmRuntime},
+    wasm_asset::WasmAsset,
+};
+
+/// Marks a mod that is being instantiated again after its WebAssembly binary changed
+#[derive(Component)]
+pub struct Reloading {
+    /// What the old instance returned from `save_state`
+    pub saved_state: Option<Box<[u8]>>,
+}
+
+pub fn reload_instances(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<WasmAsset>>,
+    mut wasm_runtime: ResMut<WasmRuntime>,
+    mut wasm_mods: Query<(Entity, &WasmMod, &WasmModStatus, Option<&mut WasmInstance>)>,
+) {
+    for event in asset_events.iter() {
+        let handle = match event {
+            AssetEvent::Created { .. } => continue,
+            AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { handle } => {
+                wasm_runtime.forget_module(handle);
+                continue;
+            }
+        };
+
+        // Mods created from this asset from now on need the new binary
+        wasm_runtime.forget_module(handle);
+
+        for (entity, wasm_mod, status, wasm_instance) in wasm_mods.iter_mut() {
+            if wasm_mod.wasm != *handle {
+                continue;
+            }
+
+            // Mods that are still loading will pick up the new binary anyway
+            if matches!(status, WasmModStatus::Loading | WasmModStatus::Unloaded) {
+                continue;
+            }
+
+            info!("Reloading mod {:?}", entity);
+
+            let saved_state = match wasm_instance {
+                Some(mut wasm_instance) => match wasm_instance.save_state(wasm_mod) {
+                    Ok(saved_state) => saved_state,
+                    Err(err) => {
+                        warn!("Could not save the state of mod {:?}: {:#}", entity, err);
+                        None
+                    }
+                },
+                None => None,
+            };
+
+            commands
+                .entity(entity)
+                .remove::<WasmInstance>()
+                .insert((WasmModStatus::Loading, Reloading { saved_state }));
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use bevy::{prelude::*, reflect::TypeUuid};
+use bevy_wasm_shared::rpc::Request;
+
+use crate::{
+    rpc::{ModRequest, QueuedResponse, Responses},
+    runtime::WasmInstance,
+};
+
+use super::ProtocolCodec;
+
+/// Send the requests mods made during their tick as [`ModRequest`] events
+pub fn receive_mod_requests<Req: Request + TypeUuid>(
+    codec: Res<ProtocolCodec>,
+    responses: Res<Responses<Req>>,
+    mut requests: EventWriter<ModRequest<Req>>,
+    mut wasm_mods: Query<(Entity, &mut WasmInstance)>,
+) {
+    let mut wasm_mods: Vec<_> = wasm_mods.iter_mut().collect();
+    wasm_mods.sort_by_key(|(entity, _)| *entity);
+
+    for (entity, mut wasm_instance) in wasm_mods {
+        for serialized_request in wasm_instance.take_channel_events(Req::TYPE_UUID) {
+            match codec.0.decode::<(u64, Req)>(&serialized_request) {
+                Ok((id, request)) => requests.send(ModRequest {
+                    source: entity,
+                    request,
+                    responder: responses.responder(entity, wasm_instance.id(), id),
+                }),
+                Err(err) => error!("Error while deserializing request: {}", err),
+            }
+        }
+    }
+}
+
+/// Queue the responses to mod requests, before the mods are ticked
+pub fn send_mod_responses<Req: Request + TypeUuid>(
+    codec: Res<ProtocolCodec>,
+    responses: Res<Responses<Req>>,
+    mut wasm_mods: Query<&mut WasmInstance>,
+) {
+    for QueuedResponse {
+        target,
+        instance,
+        id,
+        response,
+    } in responses.take()
+    {
+        // The mod may have been unloaded or reloaded since
+        let Ok(mut wasm_instance) = wasm_mods.get_mut(target) else {
+            continue;
+        };
+        if wasm_instance.id() != instance {
+            continue;
+        }
+        match codec.0.encode(&(id, response)) {
+            Ok(bytes) => wasm_instance.push_channel_events(Req::TYPE_UUID, &[bytes.into()]),
+            Err(err) => error!("Error while serializing response: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_wasm_shared::codec::Codec;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::runtime::native::test_mods;
+
+    #[derive(Serialize, Deserialize, TypeUuid)]
+    #[uuid = "4b4bdc4e-3a9c-4b8e-9d0e-6d7a4f3c2b10"]
+    struct Ping;
+
+    impl Request for Ping {
+        type Response = u32;
+    }
+
+    #[test]
+    fn responses_only_reach_the_instance_that_asked() {
+        let mut app = App::new();
+        app.init_resource::<Responses<Ping>>()
+            .insert_resource(ProtocolCodec(Codec::default()))
+            .add_system(send_mod_responses::<Ping>);
+
+        let mut runtime = test_mods::runtime();
+        let entity = test_mods::spawn_mod(&mut app.world, &mut runtime, &test_mods::echo_mod());
+        let instance = app.world.get::<WasmInstance>(entity).unwrap().id();
+
+        let responses = app.world.resource::<Responses<Ping>>();
+        responses.responder(entity, instance, 1).respond(10);
+        // Made by the mod before it was reloaded
+        responses.responder(entity, instance + 1, 2).respond(20);
+        // Made by a mod that was unloaded since
+        responses
+            .responder(Entity::from_raw(1000), 0, 3)
+            .respond(30);
+        app.update();
+
+        let channel = &app
+            .world
+            .get::<WasmInstance>(entity)
+            .unwrap()
+            .mod_state()
+            .channels_in[&Ping::TYPE_UUID];
+        let responses: Vec<(u64, u32)> = channel
+            .iter()
+            .map(|bytes| Codec::default().decode(bytes).unwrap())
+            .collect();
+        assert_eq!(responses, vec![(1, 10)]);
+        assert!(app.world.resource::<Responses<Ping>>().take().is_empty());
+    }
+}
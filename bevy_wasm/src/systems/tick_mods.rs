@@ -1,34 +1,216 @@
-use std::sync::Arc;
+use std::sync::Mutex;
 
+use anyhow::Result;
 use bevy::prelude::*;
 
-use crate::{runtime::WasmInstance, Message};
+use crate::{
+    components::{BudgetPolicy, WasmMod, WasmModStatus},
+    error::ModError,
+    events::ModFailed,
+    runtime::WasmInstance,
+    Message,
+};
+
+use super::{
+    messages::{MessagesIn, MessagesOut},
+    ProtocolCodec,
+};
+
+/// The events a mod sent during its tick, or why it failed
+type TickResult = (Entity, Result<Vec<Box<[u8]>>>);
+
+/// How [`tick_mods`] runs the mods
+#[derive(Resource)]
+pub struct TickSettings {
+    /// Tick mods concurrently on the [`ComputeTaskPool`](bevy::tasks::ComputeTaskPool)
+    pub parallel: bool,
+}
 
 pub fn tick_mods<In: Message, Out: Message>(
-    mut events_in: EventReader<In>,
-    mut events_out: EventWriter<Out>,
-    mut wasm_mods: Query<&mut WasmInstance>,
+    mut commands: Commands,
+    settings: Res<TickSettings>,
+    codec: Res<ProtocolCodec>,
+    mut events_in: MessagesIn<In>,
+    mut events_out: MessagesOut<Out>,
+    mut failed_events: EventWriter<ModFailed>,
+    mut wasm_mods: Query<(Entity, &WasmMod, &WasmModStatus, &mut WasmInstance)>,
 ) {
-    let serialized_events_in: Vec<Arc<[u8]>> = events_in
-        .iter()
-        .flat_map(|event| bincode::serialize(event))
-        .map(|bytes| bytes.into())
-        .collect();
-
-    for mut wasm_mod in wasm_mods.iter_mut() {
-        let serialized_events_out = match wasm_mod.tick(serialized_events_in.as_slice()) {
+    let events_in = events_in.serialize(codec.0);
+
+    let mut results: Vec<TickResult> = if settings.parallel {
+        let results = Mutex::new(Vec::new());
+        wasm_mods
+            .par_iter_mut()
+            .for_each_mut(|(entity, wasm_mod, status, mut wasm_instance)| {
+                if *status != WasmModStatus::Running {
+                    return;
+                }
+                let result = wasm_instance.tick(wasm_mod, &events_in.for_mod(entity));
+                results.lock().unwrap().push((entity, result));
+            });
+        results.into_inner().unwrap()
+    } else {
+        wasm_mods
+            .iter_mut()
+            .filter(|(_, _, status, _)| **status == WasmModStatus::Running)
+            .map(|(entity, wasm_mod, _, mut wasm_instance)| {
+                let result = wasm_instance.tick(wasm_mod, &events_in.for_mod(entity));
+                (entity, result)
+            })
+            .collect()
+    };
+
+    // Mods may finish in any order, so send their events in a deterministic one
+    results.sort_by_key(|(entity, _)| *entity);
+
+    for (entity, result) in results {
+        let serialized_events_out = match result {
             Ok(events) => events,
             Err(err) => {
-                error!("Error while ticking mod: {}", err);
+                match err.downcast_ref::<ModError>() {
+                    Some(ModError::BudgetExceeded { .. } | ModError::DeadlineExceeded { .. }) => {
+                        let Ok((_, wasm_mod, _, _)) = wasm_mods.get(entity) else {
+                            continue;
+                        };
+                        apply_budget_policy(
+                            &mut commands,
+                            &mut failed_events,
+                            entity,
+                            wasm_mod,
+                            &err,
+                        )
+                    }
+                    _ => {
+                        error!("Error while ticking mod, stopping it: {:#}", err);
+                        fail_mod(&mut commands, &mut failed_events, entity, &err);
+                    }
+                }
                 continue;
             }
         };
 
-        for serialized_event_out in serialized_events_out {
-            match bincode::deserialize(&serialized_event_out) {
-                Ok(event_out) => events_out.send(event_out),
-                Err(err) => error!("Error while deserializing event: {}", err),
-            }
+        events_out.send_serialized(codec.0, entity, serialized_events_out);
+    }
+}
+
+fn apply_budget_policy(
+    commands: &mut Commands,
+    failed_events: &mut EventWriter<ModFailed>,
+    entity: Entity,
+    wasm_mod: &WasmMod,
+    err: &anyhow::Error,
+) {
+    match wasm_mod.on_budget_exceeded {
+        BudgetPolicy::Skip => {
+            warn!("{}, skipping this frame", err);
+        }
+        BudgetPolicy::Suspend => {
+            error!("{}, suspending it", err);
+            fail_mod(commands, failed_events, entity, err);
+        }
+        BudgetPolicy::Despawn => {
+            error!("{}, despawning it", err);
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Stop ticking a mod, and tell the game why
+fn fail_mod(
+    commands: &mut Commands,
+    failed_events: &mut EventWriter<ModFailed>,
+    entity: Entity,
+    err: &anyhow::Error,
+) {
+    let reason = format!("{:#}", err);
+    commands
+        .entity(entity)
+        .insert(WasmModStatus::Failed(reason.clone()));
+    failed_events.send(ModFailed { entity, reason });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::{ComputeTaskPool, TaskPool};
+    use bevy_wasm_shared::codec::Codec;
+
+    use super::*;
+    use crate::{
+        events::{FromMod, ToMod},
+        runtime::native::test_mods,
+        systems::TagMessages,
+    };
+
+    /// An app that ticks `count` echo mods, and the mods' entities. Mods get `u32` messages,
+    /// which they send back as `i32` ones.
+    fn echo_app(parallel: bool, count: usize) -> (App, Vec<Entity>) {
+        ComputeTaskPool::init(TaskPool::default);
+
+        let mut app = App::new();
+        app.add_event::<u32>()
+            .add_event::<ToMod<u32>>()
+            .add_event::<i32>()
+            .add_event::<FromMod<i32>>()
+            .add_event::<ModFailed>()
+            .insert_resource(TickSettings { parallel })
+            .insert_resource(ProtocolCodec(Codec::default()))
+            .insert_resource(TagMessages(true))
+            .add_system(tick_mods::<u32, i32>);
+
+        let mut runtime = test_mods::runtime();
+        let wasm = test_mods::echo_mod();
+        let entities = (0..count)
+            .map(|_| test_mods::spawn_mod(&mut app.world, &mut runtime, &wasm))
+            .collect();
+
+        (app, entities)
+    }
+
+    /// The messages mods sent during the last update, and who sent them
+    fn messages_from_mods(app: &mut App) -> Vec<(Entity, i32)> {
+        app.world
+            .resource_mut::<Events<FromMod<i32>>>()
+            .drain()
+            .map(|FromMod { source, message }| (source, message))
+            .collect()
+    }
+
+    #[test]
+    fn parallel_ticking_sends_messages_in_entity_order() {
+        for parallel in [false, true] {
+            let (mut app, entities) = echo_app(parallel, 8);
+
+            app.world.send_event(1_u32);
+            app.world.send_event(2_u32);
+            app.update();
+
+            let expected: Vec<_> = entities
+                .iter()
+                .flat_map(|entity| [(*entity, 1), (*entity, 2)])
+                .collect();
+            assert_eq!(messages_from_mods(&mut app), expected);
         }
     }
+
+    #[test]
+    fn targeted_messages_reach_a_single_mod_after_broadcasts() {
+        let (mut app, entities) = echo_app(false, 3);
+
+        app.world.send_event(ToMod {
+            target: entities[1],
+            message: 2_u32,
+        });
+        app.world.send_event(1_u32);
+        app.update();
+
+        assert_eq!(
+            messages_from_mods(&mut app),
+            vec![
+                (entities[0], 1),
+                (entities[1], 1),
+                (entities[1], 2),
+                (entities[2], 1),
+            ]
+        );
+    }
 }
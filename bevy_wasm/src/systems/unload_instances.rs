@@ -0,0 +1,23 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{WasmMod, WasmModStatus},
+    events::ModUnloaded,
+    runtime::WasmInstance,
+};
+
+pub fn unload_instances(
+    mut commands: Commands,
+    mut removed_mods: RemovedComponents<WasmMod>,
+    mut unloaded_events: EventWriter<ModUnloaded>,
+) {
+    for entity in removed_mods.iter() {
+        // The entity is gone entirely if it was despawned
+        if let Some(mut entity_commands) = commands.get_entity(entity) {
+            entity_commands
+                .remove::<WasmInstance>()
+                .insert(WasmModStatus::Unloaded);
+        }
+        unloaded_events.send(ModUnloaded { entity });
+    }
+}
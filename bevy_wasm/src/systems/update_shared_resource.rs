@@ -1,25 +1,154 @@
-use std::{ops::Deref, sync::Arc};
+use std::{collections::VecDeque, ops::Deref, sync::Arc};
 
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
+use bevy_wasm_shared::resource_update::{diff, ResourceUpdate};
 
-use crate::{runtime::WasmInstance, SharedResource};
+use crate::{
+    components::WasmMod,
+    runtime::{WasmInstance, WasmRuntime},
+    shared_resources::DeltaEncoded,
+    SharedResource,
+};
 
+use super::ProtocolCodec;
+
+/// How many past values of a resource are kept to make patches against
+const HISTORY_LEN: usize = 8;
+
+/// The latest values of a shared resource, by version
+#[derive(Default)]
+pub struct ResourceHistory {
+    version: u64,
+    values: VecDeque<(u64, Arc<[u8]>)>,
+    /// The game removed the resource since its last value
+    removed: bool,
+}
+
+impl ResourceHistory {
+    fn push(&mut self, value: Arc<[u8]>) {
+        self.version += 1;
+        self.values.push_back((self.version, value));
+        if self.values.len() > HISTORY_LEN {
+            self.values.pop_front();
+        }
+        self.removed = false;
+    }
+
+    /// Forget the values, which patches no longer apply to
+    fn remove(&mut self) {
+        self.version += 1;
+        self.values.clear();
+        self.removed = true;
+    }
+
+    fn is_present(&self) -> bool {
+        !self.values.is_empty()
+    }
+
+    /// The latest value, whole, or the resource's removal
+    fn full(&self) -> Option<Arc<[u8]>> {
+        if self.removed {
+            let update = ResourceUpdate::Removed {
+                version: self.version,
+            };
+            return Some(update.encode().into());
+        }
+        let (version, value) = self.values.back()?;
+        Some(
+            ResourceUpdate::Full {
+                version: *version,
+                value,
+            }
+            .encode()
+            .into(),
+        )
+    }
+
+    /// A patch from an older version to the latest one, unless that version is forgotten
+    /// or the patch is no smaller than the whole value
+    fn patch_from(&self, base: u64) -> Option<Arc<[u8]>> {
+        let (version, value) = self.values.back()?;
+        let (_, old) = self.values.iter().find(|(version, _)| *version == base)?;
+        let patch = diff(old, value);
+        if patch.len() >= value.len() {
+            return None;
+        }
+        let update = ResourceUpdate::Patch {
+            version: *version,
+            base,
+            patch: &patch,
+        };
+        Some(update.encode().into())
+    }
+}
+
+/// Send the new value of a shared resource to the mods, and keep it for the mods that load later
+///
+/// Mods get the whole value, or a patch against the version they last read if the resource
+/// was shared with deltas. Mods that may not read the resource get nothing.
+/// If the game removes the resource, mods are told and new mods start without it.
 pub fn update_shared_resource<T: SharedResource>(
-    res: Res<T>,
-    mut wasm_mods: Query<&mut WasmInstance>,
+    res: Option<Res<T>>,
+    codec: Res<ProtocolCodec>,
+    delta_encoded: Option<Res<DeltaEncoded<T>>>,
+    mut history: Local<ResourceHistory>,
+    mut wasm_runtime: ResMut<WasmRuntime>,
+    mut wasm_mods: Query<(Entity, &WasmMod, &mut WasmInstance)>,
 ) {
-    if res.is_changed() {
-        let v: &T = res.deref();
-        let resource_bytes: Arc<[u8]> = match bincode::serialize(v) {
-            Ok(bytes) => bytes.into(),
-            Err(err) => {
-                error!("Error while serializing resource: {}", err);
-                return;
+    let changed = match &res {
+        Some(res) if res.is_changed() => {
+            let v: &T = res.deref();
+            match codec.0.encode(v) {
+                Ok(bytes) => history.push(bytes.into()),
+                Err(err) => {
+                    error!("Error while serializing resource: {}", err);
+                    return;
+                }
             }
-        };
 
-        for mut wasm_mod in wasm_mods.iter_mut() {
-            wasm_mod.update_resource_value::<T>(resource_bytes.clone());
+            // New and reloaded instances start with the latest value, see `WasmRuntime::create_instance`
+            if let Some(full) = history.full() {
+                wasm_runtime.set_shared_resource_value::<T>(full);
+            }
+            true
+        }
+        None if history.is_present() => {
+            history.remove();
+            wasm_runtime.remove_shared_resource_value::<T>();
+            true
         }
+        _ => false,
+    };
+
+    let mut full = None;
+    let mut patches: HashMap<u64, Option<Arc<[u8]>>> = HashMap::new();
+    for (entity, wasm_mod, mut wasm_instance) in wasm_mods.iter_mut() {
+        if !wasm_runtime.may_read_resource::<T>(entity, wasm_mod) {
+            wasm_instance.deny_resource::<T>();
+            continue;
+        }
+
+        // Mods that just got access, or lost track of the value, get it whole even if it didn't change
+        let allowed = wasm_instance.allow_resource::<T>();
+        let resync = wasm_instance.take_resource_resync::<T>() || allowed;
+        if !changed && !resync {
+            continue;
+        }
+
+        let base = match delta_encoded {
+            Some(_) if !resync => wasm_instance.resource_version::<T>(),
+            _ => None,
+        };
+        let patch = base.and_then(|base| {
+            patches
+                .entry(base)
+                .or_insert_with(|| history.patch_from(base))
+                .clone()
+        });
+        let Some(update) = patch.or_else(|| full.get_or_insert_with(|| history.full()).clone())
+        else {
+            continue;
+        };
+        wasm_instance.update_resource_value::<T>(update);
     }
 }
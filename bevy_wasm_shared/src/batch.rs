@@ -0,0 +1,111 @@
+//! Many serialized messages packed into a single buffer, so they cross the host/mod boundary in one call
+//!
+//! Each message is prefixed with its length as a little-endian `u32`.
+
+/// Size of the length prefix in front of every message
+const LEN_SIZE: usize = std::mem::size_of::<u32>();
+
+/// The number of bytes [`encode`] will need for these messages
+pub fn encoded_len<'a>(messages: impl IntoIterator<Item = &'a [u8]>) -> usize {
+    messages
+        .into_iter()
+        .map(|message| LEN_SIZE + message.len())
+        .sum()
+}
+
+/// Pack serialized messages into a single buffer
+pub fn encode<'a>(messages: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut batch = Vec::new();
+    for message in messages {
+        batch.extend_from_slice(&(message.len() as u32).to_le_bytes());
+        batch.extend_from_slice(message);
+    }
+    batch
+}
+
+/// Iterate over the serialized messages in a buffer made by [`encode`]
+///
+/// Stops early if the buffer is truncated.
+pub fn decode(batch: &[u8]) -> Decode<'_> {
+    Decode { rest: batch }
+}
+
+/// Iterator returned by [`decode`]
+#[derive(Debug, Clone)]
+pub struct Decode<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Decode<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (len, rest) = self.rest.split_at(LEN_SIZE.min(self.rest.len()));
+        let len = u32::from_le_bytes(len.try_into().ok()?) as usize;
+
+        if len > rest.len() {
+            self.rest = &[];
+            return None;
+        }
+
+        let (message, rest) = rest.split_at(len);
+        self.rest = rest;
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(messages: &[&[u8]]) -> Vec<u8> {
+        let batch = encode(messages.iter().copied());
+        assert_eq!(batch.len(), encoded_len(messages.iter().copied()));
+        assert_eq!(decode(&batch).collect::<Vec<_>>(), messages);
+        batch
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        round_trip(&[b"hello", b"", b"world"]);
+        round_trip(&[&[0; 1000]]);
+    }
+
+    #[test]
+    fn empty_batches() {
+        let batch = round_trip(&[]);
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn messages_are_length_prefixed() {
+        let batch = round_trip(&[&[7, 8]]);
+        assert_eq!(batch, vec![2, 0, 0, 0, 7, 8]);
+    }
+
+    #[test]
+    fn truncated_messages_end_the_batch() {
+        let batch = encode([&b"hello"[..], b"world"]);
+
+        let mut messages = decode(&batch[..batch.len() - 1]);
+        assert_eq!(messages.next(), Some(&b"hello"[..]));
+        assert_eq!(messages.next(), None);
+        assert_eq!(messages.next(), None);
+    }
+
+    #[test]
+    fn truncated_length_prefixes_end_the_batch() {
+        let batch = encode([&b"hello"[..], b"world"]);
+
+        let messages: Vec<_> = decode(&batch[..LEN_SIZE + 5 + 2]).collect();
+        assert_eq!(messages, vec![&b"hello"[..]]);
+    }
+
+    #[test]
+    fn malformed_lengths_end_the_batch() {
+        let mut batch = u32::MAX.to_le_bytes().to_vec();
+        batch.extend_from_slice(b"hello");
+
+        assert_eq!(decode(&batch).next(), None);
+    }
+}
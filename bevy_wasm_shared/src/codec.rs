@@ -0,0 +1,112 @@
+//! Serialization of the messages and resources passed between the game and its mods
+
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// How messages and shared resources are serialized.
+///
+/// The game and its mods must agree on the codec, so it belongs in the protocol crate next to the protocol version:
+///
+/// ```
+/// use bevy_wasm_shared::prelude::*;
+///
+/// pub const CODEC: Codec = Codec::Bincode;
+/// ```
+///
+/// [`Codec::Postcard`] and [`Codec::Json`] need the `postcard` and `json` features.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Codec {
+    /// [`bincode`](https://crates.io/crates/bincode). Fast, and the default.
+    #[default]
+    Bincode = 0,
+
+    /// [`postcard`](https://crates.io/crates/postcard). Varint encoded, so payloads are smaller.
+    #[cfg(feature = "postcard")]
+    Postcard = 1,
+
+    /// JSON. Readable by mods that aren't written in Rust, and by debugging tools.
+    #[cfg(feature = "json")]
+    Json = 2,
+}
+
+impl Codec {
+    /// Serialize a value
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Codec::Bincode => bincode::serialize(value).map_err(CodecError::Bincode),
+            #[cfg(feature = "postcard")]
+            Codec::Postcard => postcard::to_allocvec(value).map_err(CodecError::Postcard),
+            #[cfg(feature = "json")]
+            Codec::Json => serde_json::to_vec(value).map_err(CodecError::Json),
+        }
+    }
+
+    /// Deserialize a value
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        match self {
+            Codec::Bincode => bincode::deserialize(bytes).map_err(CodecError::Bincode),
+            #[cfg(feature = "postcard")]
+            Codec::Postcard => postcard::from_bytes(bytes).map_err(CodecError::Postcard),
+            #[cfg(feature = "json")]
+            Codec::Json => serde_json::from_slice(bytes).map_err(CodecError::Json),
+        }
+    }
+
+    /// Convert the codec into a u8.
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Convert a u8 into a codec, if it names one that is enabled.
+    pub fn from_u8(codec: u8) -> Option<Self> {
+        match codec {
+            0 => Some(Codec::Bincode),
+            #[cfg(feature = "postcard")]
+            1 => Some(Codec::Postcard),
+            #[cfg(feature = "json")]
+            2 => Some(Codec::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A value could not be serialized or deserialized
+#[derive(Debug)]
+pub enum CodecError {
+    /// Error from [`Codec::Bincode`]
+    Bincode(bincode::Error),
+
+    /// Error from [`Codec::Postcard`]
+    #[cfg(feature = "postcard")]
+    Postcard(postcard::Error),
+
+    /// Error from [`Codec::Json`]
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Bincode(err) => write!(f, "bincode: {}", err),
+            #[cfg(feature = "postcard")]
+            CodecError::Postcard(err) => write!(f, "postcard: {}", err),
+            #[cfg(feature = "json")]
+            CodecError::Json(err) => write!(f, "json: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodecError::Bincode(err) => Some(err),
+            #[cfg(feature = "postcard")]
+            CodecError::Postcard(err) => Some(err),
+            #[cfg(feature = "json")]
+            CodecError::Json(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! Functions the game exposes to mods, called synchronously from inside the mod's tick
+
+/// The outcome of a `call_host_function` import, as returned to the mod
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum HostCallStatus {
+    /// The function ran. Its serialized result is waiting for the mod.
+    Ok = 0,
+
+    /// The game has no function with that name
+    UnknownFunction = 1,
+
+    /// The arguments or the result could not be serialized
+    Failed = 2,
+}
+
+impl HostCallStatus {
+    /// Convert the status into a u32.
+    pub fn to_u32(self) -> u32 {
+        self as u32
+    }
+
+    /// Convert a u32 into a status. Unknown values are treated as [`HostCallStatus::Failed`].
+    pub fn from_u32(status: u32) -> Self {
+        match status {
+            0 => HostCallStatus::Ok,
+            1 => HostCallStatus::UnknownFunction,
+            _ => HostCallStatus::Failed,
+        }
+    }
+}
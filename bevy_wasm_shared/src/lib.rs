@@ -4,10 +4,17 @@
 
 #![deny(missing_docs)]
 
+pub mod batch;
+pub mod codec;
+pub mod host_function;
+pub mod resource_update;
+pub mod rpc;
 pub mod version;
 
 /// Convenience re-exports
 pub mod prelude {
+    pub use crate::codec::Codec;
+    pub use crate::rpc::Request;
     pub use crate::version;
     pub use crate::version::Version;
 }
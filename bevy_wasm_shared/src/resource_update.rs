@@ -0,0 +1,310 @@
+//! How the game sends the value of a shared resource to a mod: the whole value, a patch
+//! against a version the mod already has, or its removal
+//!
+//! An update starts with a one-byte tag and its version as a little-endian `u64`. Patches
+//! also carry the version they apply to.
+
+/// Returned by the host's `get_resource` instead of a length when the mod may not read the resource
+pub const RESOURCE_DENIED: u32 = u32::MAX;
+
+const FULL: u8 = 0;
+const PATCH: u8 = 1;
+const REMOVED: u8 = 2;
+
+/// Size of a version number
+const VERSION_SIZE: usize = std::mem::size_of::<u64>();
+
+/// Size of the offset and length in front of every segment of a patch
+const SEGMENT_HEADER_SIZE: usize = 2 * std::mem::size_of::<u32>();
+
+/// A new value of a shared resource, or its removal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceUpdate<'a> {
+    /// The whole serialized value
+    Full {
+        /// Version of the value
+        version: u64,
+        /// The serialized value
+        value: &'a [u8],
+    },
+
+    /// A patch made by [`diff`], turning the value of version `base` into this one
+    Patch {
+        /// Version of the value once patched
+        version: u64,
+        /// Version of the value the patch applies to
+        base: u64,
+        /// The patch
+        patch: &'a [u8],
+    },
+
+    /// The game removed the resource
+    Removed {
+        /// Version of the removal
+        version: u64,
+    },
+}
+
+impl<'a> ResourceUpdate<'a> {
+    /// Version of the value this update gives
+    pub fn version(&self) -> u64 {
+        match self {
+            ResourceUpdate::Full { version, .. }
+            | ResourceUpdate::Patch { version, .. }
+            | ResourceUpdate::Removed { version } => *version,
+        }
+    }
+
+    /// Serialize the update
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            ResourceUpdate::Full { version, value } => {
+                bytes.push(FULL);
+                bytes.extend_from_slice(&version.to_le_bytes());
+                bytes.extend_from_slice(value);
+            }
+            ResourceUpdate::Patch {
+                version,
+                base,
+                patch,
+            } => {
+                bytes.push(PATCH);
+                bytes.extend_from_slice(&version.to_le_bytes());
+                bytes.extend_from_slice(&base.to_le_bytes());
+                bytes.extend_from_slice(patch);
+            }
+            ResourceUpdate::Removed { version } => {
+                bytes.push(REMOVED);
+                bytes.extend_from_slice(&version.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Deserialize an update made by [`ResourceUpdate::encode`]. `None` if it's malformed.
+    pub fn decode(bytes: &'a [u8]) -> Option<Self> {
+        let (&tag, rest) = bytes.split_first()?;
+        let (version, rest) = read_u64(rest)?;
+        match tag {
+            FULL => Some(ResourceUpdate::Full {
+                version,
+                value: rest,
+            }),
+            PATCH => {
+                let (base, patch) = read_u64(rest)?;
+                Some(ResourceUpdate::Patch {
+                    version,
+                    base,
+                    patch,
+                })
+            }
+            REMOVED => Some(ResourceUpdate::Removed { version }),
+            _ => None,
+        }
+    }
+}
+
+/// Make a patch that turns `old` into `new`
+///
+/// The patch is the length of `new`, followed by the ranges of bytes that differ from `old`.
+/// It stays small as long as the value's layout doesn't shift, as with fixed-size grids and tables.
+pub fn diff(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let differs = |i: usize| old.get(i) != Some(&new[i]);
+
+    let mut segments: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < new.len() {
+        if !differs(i) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < new.len() && differs(i) {
+            i += 1;
+        }
+        match segments.last_mut() {
+            // Resending a few unchanged bytes is cheaper than starting another segment
+            Some((_, end)) if start - *end <= SEGMENT_HEADER_SIZE => *end = i,
+            _ => segments.push((start, i)),
+        }
+    }
+
+    let mut patch = (new.len() as u32).to_le_bytes().to_vec();
+    for (start, end) in segments {
+        patch.extend_from_slice(&(start as u32).to_le_bytes());
+        patch.extend_from_slice(&((end - start) as u32).to_le_bytes());
+        patch.extend_from_slice(&new[start..end]);
+    }
+    patch
+}
+
+/// Apply a patch made by [`diff`] to `old`. `None` if the patch is malformed.
+pub fn apply_patch(old: &[u8], patch: &[u8]) -> Option<Vec<u8>> {
+    let (len, mut rest) = read_u32(patch)?;
+
+    let mut new = old[..len.min(old.len())].to_vec();
+    new.resize(len, 0);
+
+    while !rest.is_empty() {
+        let (start, after_start) = read_u32(rest)?;
+        let (segment_len, after_len) = read_u32(after_start)?;
+        let end = start.checked_add(segment_len)?;
+        if end > len || segment_len > after_len.len() {
+            return None;
+        }
+        let (segment, after_segment) = after_len.split_at(segment_len);
+        new[start..end].copy_from_slice(segment);
+        rest = after_segment;
+    }
+
+    Some(new)
+}
+
+fn read_u64(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    if bytes.len() < VERSION_SIZE {
+        return None;
+    }
+    let (value, rest) = bytes.split_at(VERSION_SIZE);
+    Some((u64::from_le_bytes(value.try_into().ok()?), rest))
+}
+
+fn read_u32(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    const SIZE: usize = std::mem::size_of::<u32>();
+    if bytes.len() < SIZE {
+        return None;
+    }
+    let (value, rest) = bytes.split_at(SIZE);
+    Some((u32::from_le_bytes(value.try_into().ok()?) as usize, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(old: &[u8], new: &[u8]) -> Vec<u8> {
+        let patch = diff(old, new);
+        assert_eq!(apply_patch(old, &patch).as_deref(), Some(new));
+        patch
+    }
+
+    /// The offset and length of every segment of a patch
+    fn segments(patch: &[u8]) -> Vec<(usize, usize)> {
+        let (_, mut rest) = read_u32(patch).unwrap();
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            let (start, after_start) = read_u32(rest).unwrap();
+            let (len, after_len) = read_u32(after_start).unwrap();
+            segments.push((start, len));
+            rest = &after_len[len..];
+        }
+        segments
+    }
+
+    #[test]
+    fn identical_values_have_no_segments() {
+        let patch = round_trip(&[1, 2, 3, 4], &[1, 2, 3, 4]);
+        assert_eq!(patch.len(), 4);
+        assert!(segments(&patch).is_empty());
+    }
+
+    #[test]
+    fn growth() {
+        let patch = round_trip(&[1, 2, 3], &[1, 2, 3, 4, 5]);
+        assert_eq!(segments(&patch), vec![(3, 2)]);
+    }
+
+    #[test]
+    fn shrink() {
+        let patch = round_trip(&[1, 2, 3, 4, 5], &[1, 9, 3]);
+        assert_eq!(segments(&patch), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn empty_values() {
+        round_trip(&[], &[]);
+        round_trip(&[], &[1, 2, 3]);
+        let patch = round_trip(&[1, 2, 3], &[]);
+        assert!(segments(&patch).is_empty());
+    }
+
+    #[test]
+    fn small_gaps_are_merged() {
+        let old = [0; 32];
+
+        let mut new = old;
+        new[0] = 1;
+        new[1 + SEGMENT_HEADER_SIZE] = 1;
+        let patch = round_trip(&old, &new);
+        assert_eq!(segments(&patch), vec![(0, 2 + SEGMENT_HEADER_SIZE)]);
+
+        let mut new = old;
+        new[0] = 1;
+        new[2 + SEGMENT_HEADER_SIZE] = 1;
+        let patch = round_trip(&old, &new);
+        assert_eq!(segments(&patch), vec![(0, 1), (2 + SEGMENT_HEADER_SIZE, 1)]);
+    }
+
+    #[test]
+    fn malformed_patches() {
+        let old = [0; 8];
+        let patch = diff(&old, &[0, 1, 2, 3, 4, 5, 6, 7]);
+
+        // Truncated length, segment header, or segment
+        assert_eq!(apply_patch(&old, &patch[..3]), None);
+        assert_eq!(apply_patch(&old, &patch[..6]), None);
+        assert_eq!(apply_patch(&old, &patch[..patch.len() - 1]), None);
+
+        // Segments past the end of the new value
+        let mut patch = 4u32.to_le_bytes().to_vec();
+        patch.extend_from_slice(&2u32.to_le_bytes());
+        patch.extend_from_slice(&3u32.to_le_bytes());
+        patch.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(apply_patch(&old, &patch), None);
+
+        // Offsets that overflow
+        let mut patch = 4u32.to_le_bytes().to_vec();
+        patch.extend_from_slice(&u32::MAX.to_le_bytes());
+        patch.extend_from_slice(&1u32.to_le_bytes());
+        patch.push(1);
+        assert_eq!(apply_patch(&old, &patch), None);
+    }
+
+    #[test]
+    fn updates_round_trip() {
+        let updates = [
+            ResourceUpdate::Full {
+                version: 1,
+                value: &[1, 2, 3],
+            },
+            ResourceUpdate::Full {
+                version: 2,
+                value: &[],
+            },
+            ResourceUpdate::Patch {
+                version: 4,
+                base: 3,
+                patch: &[0, 0, 0, 0],
+            },
+            ResourceUpdate::Removed { version: u64::MAX },
+        ];
+        for update in updates {
+            assert_eq!(ResourceUpdate::decode(&update.encode()), Some(update));
+        }
+    }
+
+    #[test]
+    fn malformed_updates() {
+        assert_eq!(ResourceUpdate::decode(&[]), None);
+        assert_eq!(ResourceUpdate::decode(&[FULL, 1, 0, 0]), None);
+        assert_eq!(ResourceUpdate::decode(&[3, 1, 0, 0, 0, 0, 0, 0, 0]), None);
+
+        let patch = ResourceUpdate::Patch {
+            version: 2,
+            base: 1,
+            patch: &[],
+        };
+        let encoded = patch.encode();
+        assert_eq!(ResourceUpdate::decode(&encoded[..encoded.len() - 1]), None);
+    }
+}
@@ -0,0 +1,12 @@
+//! Requests mods send to the game, each answered with a response
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A request a mod can send to the game, which answers it with a [`Request::Response`]
+///
+/// Requests and their responses travel on a channel identified by the request type's `TypeUuid`.
+/// Each call is tagged with an id, so the response reaches the call that asked for it.
+pub trait Request: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// What the game answers with
+    type Response: Serialize + DeserializeOwned + Send + Sync + 'static;
+}
@@ -40,6 +40,26 @@ impl Version {
     }
 }
 
+/// The version of the functions and formats the game and its mods talk to each other with,
+/// as opposed to the game's own protocol [`Version`]
+///
+/// Bumped whenever a mod built against an older `bevy_wasm_sys` would misread the game, or the
+/// other way around. Mods export it with `export_protocol_version!`, and the game refuses to run
+/// mods built for another one, or that don't export it.
+pub const FFI_VERSION: u32 = 2;
+
+/// The default protocol version checker used by both the game and its mods
+///
+/// Versions are compatible when their names and major versions match.
+///
+/// i.e. `1.0.0` is compatible with `1.1.0` but not `2.0.0`
+pub fn default_version_checker(host_version: Version, mod_version: Version) -> bool {
+    // Check that the names match
+    host_version.name_hash == mod_version.name_hash
+        // Check that the major versions match
+        && host_version.major == mod_version.major
+}
+
 /// Generate a new Version from the current crate's version.
 #[macro_export]
 macro_rules! version(
@@ -0,0 +1,19 @@
+//! The codec [`events`](crate::events) and shared resources are serialized with. Must match the game's.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use bevy_wasm_shared::codec::Codec;
+
+static CODEC: AtomicU8 = AtomicU8::new(0);
+
+/// Serialize messages and shared resources with `codec` instead of bincode
+///
+/// `FFIPlugin::with_codec` calls this for you. Mods without Bevy should call it in `build_app`.
+pub fn set_codec(codec: Codec) {
+    CODEC.store(codec.to_u8(), Ordering::Relaxed);
+}
+
+/// The codec messages and shared resources are serialized with
+pub fn codec() -> Codec {
+    Codec::from_u8(CODEC.load(Ordering::Relaxed)).unwrap_or_default()
+}
@@ -2,62 +2,174 @@
 
 use std::{
     any::{Any, TypeId},
-    collections::HashMap,
-    fmt::Debug,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Debug},
     marker::PhantomData,
-    ops::Deref,
+    ops::{Deref, DerefMut},
 };
 
-use bevy_ecs::{prelude::*, system::SystemParam};
+use bevy_ecs::{
+    component::Tick,
+    prelude::*,
+    system::{SystemChangeTick, SystemParam},
+};
 use bevy_reflect::TypeUuid;
+use bevy_wasm_shared::resource_update::{apply_patch, ResourceUpdate, RESOURCE_DENIED};
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::error;
+use crate::{codec::codec, error};
 
 /// A resource that can be shared from the Host
 pub trait SharedResource: Resource + Default + Serialize + DeserializeOwned + TypeUuid {}
 
 impl<T: Resource + Default + Serialize + DeserializeOwned + TypeUuid> SharedResource for T {}
 
-/// Get the value of a resource from the host
-pub fn get_resource<T: SharedResource>() -> Option<T> {
-    let (uuid_0, uuid_1) = T::TYPE_UUID.as_u64_pair();
+/// Why a mod couldn't read a resource from the host
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceError {
+    /// The game doesn't let this mod read the resource, see `WasmPlugin::with_resource_access`
+    Denied,
 
-    let mut buffer = [0; 1024];
+    /// The mod didn't insert the resource into [`ExternResources`]
+    NotShared,
 
-    let len = unsafe {
-        // put serialized resource into buffer
-        crate::ffi::get_resource(uuid_0, uuid_1, buffer.as_mut_ptr(), buffer.len())
-    };
+    /// The game hasn't sent the resource yet, or removed it
+    Absent,
+}
 
-    if len == 0 {
-        return None;
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceError::Denied => write!(f, "the game doesn't let this mod read the resource"),
+            ResourceError::NotShared => write!(f, "the resource was not shared with the mod"),
+            ResourceError::Absent => write!(f, "the game hasn't sent the resource, or removed it"),
+        }
     }
+}
 
-    if len > buffer.len() {
-        error!("Serialized resource is larger than buffer");
-        return None;
-    }
+impl std::error::Error for ResourceError {}
 
-    let resource_bytes = &buffer[..len];
+/// A change to a resource, sent by the host
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResourceChange<T> {
+    /// The game inserted or changed the resource
+    Value(T),
+
+    /// The game removed the resource
+    Removed,
+}
 
-    match bincode::deserialize(resource_bytes) {
-        Ok(resource) => Some(resource),
+/// Get the new value of a resource from the host, or its removal
+///
+/// `Ok(None)` if nothing changed since the last call.
+pub fn get_resource<T: SharedResource>() -> Result<Option<ResourceChange<T>>, ResourceError> {
+    let (uuid_0, uuid_1) = T::TYPE_UUID.as_u64_pair();
+
+    let mut denied = false;
+    let update_bytes = crate::ffi::read_from_host(|buffer, len| unsafe {
+        // put serialized resource update into buffer
+        match crate::ffi::get_resource(uuid_0, uuid_1, buffer, len) {
+            len if len == RESOURCE_DENIED as usize => {
+                denied = true;
+                0
+            }
+            len => len,
+        }
+    });
+    if denied {
+        return Err(ResourceError::Denied);
+    }
+    let resource_bytes = match update_bytes.and_then(|bytes| apply_update((uuid_0, uuid_1), &bytes))
+    {
+        Some(ResourceChange::Value(resource_bytes)) => resource_bytes,
+        Some(ResourceChange::Removed) => return Ok(Some(ResourceChange::Removed)),
+        None => return Ok(None),
+    };
+
+    match codec().decode(&resource_bytes) {
+        Ok(resource) => Ok(Some(ResourceChange::Value(resource))),
         Err(err) => {
             error!("Failed to deserialize resource from host: {}", err);
-            None
+            Ok(None)
         }
     }
 }
 
+/// A serialized resource value and its version
+type VersionedValue = (u64, Vec<u8>);
+
+thread_local! {
+    /// The last value of each resource received from the host, which patches apply to
+    static SYNCED_VALUES: RefCell<BTreeMap<(u64, u64), VersionedValue>> =
+        const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Turn an update from the host into the resource's serialized value, or its removal
+///
+/// Asks the host for the whole value if the update doesn't apply to the value we have.
+fn apply_update(uuid: (u64, u64), update_bytes: &[u8]) -> Option<ResourceChange<Vec<u8>>> {
+    SYNCED_VALUES.with(|synced_values| {
+        let mut synced_values = synced_values.borrow_mut();
+
+        let update = ResourceUpdate::decode(update_bytes);
+        if let Some(ResourceUpdate::Removed { .. }) = update {
+            synced_values.remove(&uuid);
+            return Some(ResourceChange::Removed);
+        }
+
+        let value = match update {
+            Some(ResourceUpdate::Full { value, .. }) => Some(value.to_vec()),
+            Some(ResourceUpdate::Patch { base, patch, .. }) => match synced_values.get(&uuid) {
+                Some((version, old)) if *version == base => apply_patch(old, patch),
+                _ => None,
+            },
+            Some(ResourceUpdate::Removed { .. }) | None => None,
+        };
+
+        let (Some(update), Some(value)) = (update, value) else {
+            error!("Resource update from host doesn't apply, asking for the whole value");
+            synced_values.remove(&uuid);
+            unsafe { crate::ffi::request_resource_resync(uuid.0, uuid.1) };
+            return None;
+        };
+
+        synced_values.insert(uuid, (update.version(), value.clone()));
+        Some(ResourceChange::Value(value))
+    })
+}
+
+/// Send a new value of a resource to the host
+///
+/// The game must share the resource with `WasmPlugin::share_resource_mut`, and may drop the
+/// value according to its conflict policy, in which case it sends its own value back.
+pub fn store_resource<T: SharedResource>(value: &T) {
+    let (uuid_0, uuid_1) = T::TYPE_UUID.as_u64_pair();
+
+    let encoded = match codec().encode(value) {
+        Ok(encoded) => encoded,
+        Err(err) => {
+            error!("Failed to serialize resource: {}", err);
+            return;
+        }
+    };
+
+    unsafe { crate::ffi::store_resource(uuid_0, uuid_1, encoded.as_ptr(), encoded.len()) };
+}
+
 trait AsAny {
     fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
 impl<T: Any> AsAny for T {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 trait AnyResource: AsAny + Any + Resource + Send + Sync + 'static {}
@@ -68,45 +180,128 @@ impl dyn AnyResource {
     fn downcast_ref<T: AnyResource>(&self) -> Option<&T> {
         self.as_any().downcast_ref::<T>()
     }
+
+    fn downcast_mut<T: AnyResource>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut::<T>()
+    }
 }
 
+type FetchResult = Result<Option<ResourceChange<Box<dyn AnyResource>>>, ResourceError>;
+
 trait ResourceFetch: Send + Sync {
-    fn fetch(&mut self) -> Option<Box<dyn AnyResource>>;
+    fn fetch(&mut self) -> FetchResult;
+    fn store(&self, value: &dyn AnyResource);
 }
 
 struct ExternResourceFetchImpl<T: SharedResource>(PhantomData<T>);
 
 impl<T: SharedResource> ResourceFetch for ExternResourceFetchImpl<T> {
-    fn fetch(&mut self) -> Option<Box<dyn AnyResource>> {
-        Some(Box::new(get_resource::<T>()?))
+    fn fetch(&mut self) -> FetchResult {
+        Ok(get_resource::<T>()?.map(|change| match change {
+            ResourceChange::Value(value) => ResourceChange::Value(Box::new(value) as Box<_>),
+            ResourceChange::Removed => ResourceChange::Removed,
+        }))
+    }
+
+    fn store(&self, value: &dyn AnyResource) {
+        if let Some(value) = value.downcast_ref::<T>() {
+            store_resource(value);
+        }
     }
 }
 
 struct ExternResourceValue {
-    value: Box<dyn AnyResource>,
+    /// `None` until the game sends the resource, and after it removes it
+    value: Option<Box<dyn AnyResource>>,
+    /// The resource's default value, which [`ExternRes`] dereferences to while `value` is `None`
+    default: Box<dyn AnyResource>,
     fetcher: Box<dyn ResourceFetch>,
+    /// Changed through [`ExternResMut`] since it was last sent to the host
+    changed: bool,
+    /// The game doesn't let this mod read the resource
+    denied: bool,
+    /// When the resource last got a new value, from the game or through [`ExternResMut`]
+    last_changed: Option<Tick>,
+    /// When the resource last arrived after being absent
+    added: Option<Tick>,
+    /// When the game last removed the resource
+    removed: Option<Tick>,
 }
 
 impl ExternResourceValue {
-    pub fn init<T: SharedResource>() -> Self {
-        Self {
-            value: match get_resource::<T>() {
-                Some(v) => Box::new(v),
-                None => Box::<T>::default(),
-            },
+    pub fn init<T: SharedResource>(change_tick: u32) -> Self {
+        let mut resource_value = Self {
+            value: None,
+            default: Box::new(T::default()),
             fetcher: Box::new(ExternResourceFetchImpl::<T>(PhantomData)),
-        }
+            changed: false,
+            denied: false,
+            last_changed: None,
+            added: None,
+            removed: None,
+        };
+        resource_value.fetch(change_tick);
+        resource_value
     }
 
-    pub fn fetch(&mut self) {
-        if let Some(new_value) = self.fetcher.fetch() {
-            self.value = new_value;
+    pub fn fetch(&mut self, change_tick: u32) {
+        match self.fetcher.fetch() {
+            Ok(Some(ResourceChange::Value(new_value))) => {
+                if self.value.replace(new_value).is_none() {
+                    self.added = Some(Tick::new(change_tick));
+                }
+                self.last_changed = Some(Tick::new(change_tick));
+                self.denied = false;
+            }
+            Ok(Some(ResourceChange::Removed)) => {
+                if self.value.take().is_some() {
+                    self.removed = Some(Tick::new(change_tick));
+                }
+                self.changed = false;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                if !self.denied {
+                    error!("Failed to get resource from host: {}", err);
+                }
+                self.denied = true;
+            }
         }
     }
 
-    pub fn downcast_ref<T: Resource + Serialize + DeserializeOwned>(&self) -> Option<&T> {
-        let boxed = self.value.as_ref();
-        (boxed as &(dyn AnyResource + 'static)).downcast_ref::<T>()
+    pub fn downcast_ref<T: Resource + Serialize + DeserializeOwned>(
+        &self,
+    ) -> Result<&T, ResourceError> {
+        let boxed = self.value.as_ref().ok_or(ResourceError::Absent)?.as_ref();
+        (boxed as &(dyn AnyResource + 'static))
+            .downcast_ref::<T>()
+            .ok_or(ResourceError::NotShared)
+    }
+
+    pub fn downcast_default<T: Resource + Serialize + DeserializeOwned>(
+        &self,
+    ) -> Result<&T, ResourceError> {
+        (self.default.as_ref() as &(dyn AnyResource + 'static))
+            .downcast_ref::<T>()
+            .ok_or(ResourceError::NotShared)
+    }
+
+    pub fn downcast_mut<T: Resource + Serialize + DeserializeOwned>(
+        &mut self,
+    ) -> Result<&mut T, ResourceError> {
+        let boxed = self.value.as_mut().ok_or(ResourceError::Absent)?.as_mut();
+        (boxed as &mut (dyn AnyResource + 'static))
+            .downcast_mut::<T>()
+            .ok_or(ResourceError::NotShared)
+    }
+
+    pub fn store(&mut self) {
+        if !std::mem::take(&mut self.changed) || self.denied {
+            return;
+        }
+        if let Some(value) = &self.value {
+            self.fetcher.store(value.as_ref());
+        }
     }
 }
 
@@ -114,6 +309,8 @@ impl ExternResourceValue {
 #[derive(Resource)]
 pub struct ExternResources {
     resources: HashMap<TypeId, ExternResourceValue>,
+    /// The latest world change tick seen, when fetching resources or changing them
+    change_tick: u32,
 }
 
 impl Debug for ExternResources {
@@ -130,22 +327,110 @@ impl ExternResources {
     pub fn new() -> Self {
         Self {
             resources: HashMap::new(),
+            change_tick: 0,
         }
     }
 
     pub fn insert<T: SharedResource>(&mut self) {
-        self.resources
-            .insert(TypeId::of::<T>(), ExternResourceValue::init::<T>());
+        self.resources.insert(
+            TypeId::of::<T>(),
+            ExternResourceValue::init::<T>(self.change_tick),
+        );
+    }
+
+    pub fn fetch_all(&mut self, change_tick: u32) {
+        self.change_tick = change_tick;
+        for resource_value in self.resources.values_mut() {
+            resource_value.fetch(change_tick);
+        }
     }
 
-    pub fn fetch_all(&mut self) {
+    pub fn store_all(&mut self) {
         for resource_value in self.resources.values_mut() {
-            resource_value.fetch();
+            resource_value.store();
         }
     }
 
     pub fn get<T: Resource + Serialize + DeserializeOwned>(&self) -> Option<&T> {
-        self.resources.get(&TypeId::of::<T>())?.downcast_ref()
+        self.try_get().ok()
+    }
+
+    /// Get the resource, or why it can't be read
+    pub fn try_get<T: Resource + Serialize + DeserializeOwned>(&self) -> Result<&T, ResourceError> {
+        let resource_value = self
+            .resources
+            .get(&TypeId::of::<T>())
+            .ok_or(ResourceError::NotShared)?;
+        if resource_value.denied {
+            return Err(ResourceError::Denied);
+        }
+        resource_value.downcast_ref()
+    }
+
+    /// Get the resource, or its default value while the game hasn't sent it, removed it, or doesn't
+    /// let this mod read it
+    fn get_or_default<T: Resource + Serialize + DeserializeOwned>(
+        &self,
+    ) -> Result<&T, ResourceError> {
+        match self.try_get::<T>() {
+            Err(ResourceError::Absent | ResourceError::Denied) => self
+                .resources
+                .get(&TypeId::of::<T>())
+                .ok_or(ResourceError::NotShared)?
+                .downcast_default(),
+            result => result,
+        }
+    }
+
+    /// Get the resource, marking it as changed so it is sent to the host at the end of the frame
+    pub fn get_mut<T: Resource + Serialize + DeserializeOwned>(&mut self) -> Option<&mut T> {
+        self.try_get_mut().ok()
+    }
+
+    /// Get the resource to change it, or why it can't be read
+    pub fn try_get_mut<T: Resource + Serialize + DeserializeOwned>(
+        &mut self,
+    ) -> Result<&mut T, ResourceError> {
+        let resource_value = self
+            .resources
+            .get_mut(&TypeId::of::<T>())
+            .ok_or(ResourceError::NotShared)?;
+        if resource_value.denied {
+            return Err(ResourceError::Denied);
+        }
+        if resource_value.value.is_none() {
+            return Err(ResourceError::Absent);
+        }
+        resource_value.changed = true;
+        resource_value.last_changed = Some(Tick::new(self.change_tick));
+        resource_value.downcast_mut()
+    }
+
+    /// Whether the resource got a new value since the system last ran, from the game or
+    /// through [`ExternResMut`]
+    pub fn is_changed<T: Resource>(&self, ticks: &SystemChangeTick) -> bool {
+        self.is_newer::<T>(ticks, |resource_value| resource_value.last_changed)
+    }
+
+    /// Whether the resource arrived after being absent, since the system last ran
+    pub fn is_added<T: Resource>(&self, ticks: &SystemChangeTick) -> bool {
+        self.is_newer::<T>(ticks, |resource_value| resource_value.added)
+    }
+
+    /// Whether the game removed the resource since the system last ran
+    pub fn is_removed<T: Resource>(&self, ticks: &SystemChangeTick) -> bool {
+        self.is_newer::<T>(ticks, |resource_value| resource_value.removed)
+    }
+
+    fn is_newer<T: Resource>(
+        &self,
+        ticks: &SystemChangeTick,
+        tick: impl FnOnce(&ExternResourceValue) -> Option<Tick>,
+    ) -> bool {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .and_then(tick)
+            .is_some_and(|tick| tick.is_newer_than(ticks.last_change_tick(), ticks.change_tick()))
     }
 }
 
@@ -156,9 +441,13 @@ impl Default for ExternResources {
 }
 
 /// Use a resource from the host game
+///
+/// Dereferencing it gives the resource's default value while the game hasn't sent it, removed it,
+/// or doesn't let this mod read it. Use [`ExternRes::get`] or [`ExternRes::try_get`] to tell.
 #[derive(SystemParam)]
 pub struct ExternRes<'w, 's, T: Resource + Serialize + DeserializeOwned> {
     res: Res<'w, ExternResources>,
+    ticks: SystemChangeTick,
     #[system_param(ignore)]
     t: PhantomData<T>,
     #[system_param(ignore)]
@@ -167,7 +456,7 @@ pub struct ExternRes<'w, 's, T: Resource + Serialize + DeserializeOwned> {
 
 impl<'w, 's, T: Debug + Resource + Serialize + DeserializeOwned> Debug for ExternRes<'w, 's, T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.deref().fmt(f)
+        self.try_get().fmt(f)
     }
 }
 
@@ -176,26 +465,148 @@ impl<'w, 's, T: Resource + Serialize + DeserializeOwned> ExternRes<'w, 's, T> {
     pub fn get(&self) -> Option<&T> {
         self.res.get::<T>()
     }
+
+    /// Get the resource, or why it can't be read
+    pub fn try_get(&self) -> Result<&T, ResourceError> {
+        self.res.try_get::<T>()
+    }
+
+    /// Whether the resource got a new value since the system last ran, from the game or
+    /// through [`ExternResMut`]
+    pub fn is_changed(&self) -> bool {
+        ExternResources::is_changed::<T>(&self.res, &self.ticks)
+    }
+
+    /// Whether the resource arrived after being absent, since the system last ran
+    pub fn is_added(&self) -> bool {
+        ExternResources::is_added::<T>(&self.res, &self.ticks)
+    }
+
+    /// Whether the game removed the resource since the system last ran
+    pub fn is_removed(&self) -> bool {
+        ExternResources::is_removed::<T>(&self.res, &self.ticks)
+    }
 }
 
 impl<'w, 's, T: Resource + Serialize + DeserializeOwned> Deref for ExternRes<'w, 's, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        match self.get() {
-            Some(v) => v,
-            None => {
-                error!(
-                    "FATAL: Resource was not shared with mod: {}",
-                    std::any::type_name::<T>()
-                );
-                panic!();
-            }
+        match self.res.get_or_default::<T>() {
+            Ok(v) => v,
+            Err(err) => unavailable::<T>(err),
         }
     }
 }
 
+/// Change a resource from the host game
+///
+/// The game must share it with `WasmPlugin::share_resource_mut`. Changes are sent to the game
+/// at the end of the frame, and the game may drop them according to its conflict policy.
+///
+/// Like [`ExternRes`], dereferencing it gives the default value while the resource is unavailable.
+/// Changing it through `DerefMut` then panics, use [`ExternResMut::get_mut`] if the game may remove it.
+#[derive(SystemParam)]
+pub struct ExternResMut<'w, 's, T: Resource + Serialize + DeserializeOwned> {
+    res: ResMut<'w, ExternResources>,
+    ticks: SystemChangeTick,
+    #[system_param(ignore)]
+    t: PhantomData<T>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's, T: Debug + Resource + Serialize + DeserializeOwned> Debug for ExternResMut<'w, 's, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.try_get().fmt(f)
+    }
+}
+
+impl<'w, 's, T: Resource + Serialize + DeserializeOwned> ExternResMut<'w, 's, T> {
+    /// Get the resource
+    pub fn get(&self) -> Option<&T> {
+        self.res.get::<T>()
+    }
+
+    /// Get the resource to change it
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.try_get_mut().ok()
+    }
+
+    /// Get the resource, or why it can't be read
+    pub fn try_get(&self) -> Result<&T, ResourceError> {
+        self.res.try_get::<T>()
+    }
+
+    /// Get the resource to change it, or why it can't be read
+    pub fn try_get_mut(&mut self) -> Result<&mut T, ResourceError> {
+        // Systems that run later this frame see the change
+        self.res.change_tick = self.ticks.change_tick();
+        self.res.try_get_mut::<T>()
+    }
+
+    /// Whether the resource got a new value since the system last ran, from the game or
+    /// through [`ExternResMut`]
+    pub fn is_changed(&self) -> bool {
+        ExternResources::is_changed::<T>(&self.res, &self.ticks)
+    }
+
+    /// Whether the resource arrived after being absent, since the system last ran
+    pub fn is_added(&self) -> bool {
+        ExternResources::is_added::<T>(&self.res, &self.ticks)
+    }
+
+    /// Whether the game removed the resource since the system last ran
+    pub fn is_removed(&self) -> bool {
+        ExternResources::is_removed::<T>(&self.res, &self.ticks)
+    }
+}
+
+impl<'w, 's, T: Resource + Serialize + DeserializeOwned> Deref for ExternResMut<'w, 's, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        match self.res.get_or_default::<T>() {
+            Ok(v) => v,
+            Err(err) => unavailable::<T>(err),
+        }
+    }
+}
+
+impl<'w, 's, T: Resource + Serialize + DeserializeOwned> DerefMut for ExternResMut<'w, 's, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self.try_get_mut() {
+            Ok(v) => v,
+            Err(err) => unavailable::<T>(err),
+        }
+    }
+}
+
+/// A run condition that is `true` if the resource got a new value since the system last ran,
+/// from the game or through [`ExternResMut`]
+///
+/// ```ignore
+/// app.add_system(rebuild_pathfinding.run_if(extern_resource_changed::<MapGrid>()));
+/// ```
+pub fn extern_resource_changed<T>() -> impl FnMut(ExternRes<T>) -> bool + Clone
+where
+    T: Resource + Serialize + DeserializeOwned,
+{
+    move |res: ExternRes<T>| res.is_changed()
+}
+
+fn unavailable<T>(err: ResourceError) -> ! {
+    error!(
+        "FATAL: Can't read resource {}: {}",
+        std::any::type_name::<T>(),
+        err
+    );
+    panic!();
+}
+
 /// Convenience re-exports
 pub mod prelude {
-    pub use super::ExternRes;
+    pub use super::{
+        extern_resource_changed, ExternRes, ExternResMut, ResourceChange, ResourceError,
+    };
 }
@@ -1,6 +1,7 @@
 //! ECS types
 
 pub mod extern_res;
+pub mod persistent;
 
 /// Convenience re-exports
 pub mod prelude {
@@ -0,0 +1,150 @@
+//! Keep a mod's resources across hot reloads
+//!
+//! Register resources with [`FFIPlugin::persist_resource`](crate::ffi_plugin::FFIPlugin::persist_resource).
+//! When the mod is reloaded, the host calls `save_state` on the old instance
+//! and hands the bytes to `restore_state` on the new one.
+
+use std::{collections::HashMap, ffi::c_void};
+
+use bevy_app::App;
+use bevy_ecs::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error;
+
+/// A resource that can be kept across hot reloads
+pub trait PersistentResource: Resource + Serialize + DeserializeOwned {}
+
+impl<T: Resource + Serialize + DeserializeOwned> PersistentResource for T {}
+
+/// Saves and restores a single persistent resource
+#[derive(Clone, Copy)]
+pub(crate) struct PersistentResourceEntry {
+    name: &'static str,
+    save: fn(&World) -> Option<Vec<u8>>,
+    restore: fn(&mut World, &[u8]),
+}
+
+impl PersistentResourceEntry {
+    pub fn new<T: PersistentResource>() -> Self {
+        Self {
+            name: std::any::type_name::<T>(),
+            save: save_resource::<T>,
+            restore: restore_resource::<T>,
+        }
+    }
+}
+
+fn save_resource<T: PersistentResource>(world: &World) -> Option<Vec<u8>> {
+    let resource = world.get_resource::<T>()?;
+    match bincode::serialize(resource) {
+        Ok(bytes) => Some(bytes),
+        Err(err) => {
+            error!("Failed to serialize persistent resource: {}", err);
+            None
+        }
+    }
+}
+
+fn restore_resource<T: PersistentResource>(world: &mut World, bytes: &[u8]) {
+    match bincode::deserialize::<T>(bytes) {
+        Ok(resource) => world.insert_resource(resource),
+        Err(err) => error!("Failed to deserialize persistent resource: {}", err),
+    }
+}
+
+/// The resources registered as persistent
+#[derive(Resource, Default)]
+pub(crate) struct PersistentResources {
+    entries: Vec<PersistentResourceEntry>,
+}
+
+impl PersistentResources {
+    pub(crate) fn new(entries: Vec<PersistentResourceEntry>) -> Self {
+        Self { entries }
+    }
+}
+
+/// State handed over by the previous instance, waiting to be applied
+#[derive(Resource)]
+struct SavedState(HashMap<String, Vec<u8>>);
+
+/// Called by the host on the old instance before it is hot reloaded.
+///
+/// Serializes every persistent resource and hands the bytes to the host.
+///
+/// # Safety
+///
+/// `app` is assumed to be a valid pointer to an [`App`].
+#[no_mangle]
+pub unsafe extern "C" fn save_state(app: *mut c_void) {
+    if app.is_null() {
+        return;
+    }
+
+    let app = &*(app as *mut App);
+    let Some(persistent_resources) = app.world.get_resource::<PersistentResources>() else {
+        return;
+    };
+
+    let state: HashMap<&str, Vec<u8>> = persistent_resources
+        .entries
+        .iter()
+        .filter_map(|entry| Some((entry.name, (entry.save)(&app.world)?)))
+        .collect();
+
+    let encoded = match bincode::serialize(&state) {
+        Ok(encoded) => encoded,
+        Err(err) => {
+            error!("Failed to serialize mod state: {}", err);
+            return;
+        }
+    };
+
+    crate::ffi::store_state(encoded.as_ptr(), encoded.len());
+}
+
+/// Called by the host on the new instance after it is hot reloaded.
+///
+/// Fetches the state saved by the previous instance. It is applied at the start of the next frame,
+/// after startup systems have run, so restored resources replace the ones they inserted.
+///
+/// # Safety
+///
+/// `app` is assumed to be a valid pointer to an [`App`].
+#[no_mangle]
+pub unsafe extern "C" fn restore_state(app: *mut c_void) {
+    if app.is_null() {
+        return;
+    }
+
+    let app = &mut *(app as *mut App);
+    let Some(buffer) =
+        crate::ffi::read_from_host(|buffer, len| crate::ffi::get_saved_state(buffer, len))
+    else {
+        return;
+    };
+
+    match bincode::deserialize(&buffer) {
+        Ok(state) => {
+            app.insert_resource(SavedState(state));
+        }
+        Err(err) => error!("Failed to deserialize mod state: {}", err),
+    }
+}
+
+/// Insert the resources saved by the previous instance
+pub(crate) fn apply_saved_state(world: &mut World) {
+    let Some(SavedState(state)) = world.remove_resource::<SavedState>() else {
+        return;
+    };
+    let Some(persistent_resources) = world.get_resource::<PersistentResources>() else {
+        return;
+    };
+
+    for entry in persistent_resources.entries.clone() {
+        if let Some(bytes) = state.get(entry.name) {
+            (entry.restore)(world, bytes);
+        }
+    }
+}
@@ -1,12 +1,14 @@
-//! Event functions. [`send_event`] and [`get_next_event`]
+//! Event functions. [`send_event`] and [`get_next_event`], or [`send_events`] and [`get_events`] to move a whole frame's worth at once.
+//! [`send_channel_events`] and [`get_channel_events`] do the same on additional channels.
 
+use bevy_wasm_shared::batch;
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::error;
+use crate::{codec::codec, error};
 
 /// Send an event to the host.
 pub fn send_event<T: Serialize>(event: &T) {
-    let encoded: Vec<u8> = match bincode::serialize(&event) {
+    let encoded: Vec<u8> = match codec().encode(event) {
         Ok(encoded) => encoded,
         Err(err) => {
             error!("Failed to serialize event: {}", err);
@@ -23,20 +25,11 @@ pub fn send_event<T: Serialize>(event: &T) {
 
 /// Get the next event from the host.
 pub fn get_next_event<T: DeserializeOwned>() -> Option<T> {
-    let mut buffer = [0; 1024];
+    let buffer = crate::ffi::read_from_host(|buffer, len| unsafe {
+        crate::ffi::get_next_event(buffer, len)
+    })?;
 
-    let len = unsafe { crate::ffi::get_next_event(buffer.as_mut_ptr(), buffer.len()) };
-
-    if len == 0 {
-        return None;
-    }
-
-    if len > buffer.len() {
-        error!("Serialized event is larger than buffer");
-        return None;
-    }
-
-    match bincode::deserialize(&buffer[..len]) {
+    match codec().decode(&buffer) {
         Ok(event) => Some(event),
         Err(err) => {
             error!("Failed to deserialize event from host: {}", err);
@@ -44,3 +37,85 @@ pub fn get_next_event<T: DeserializeOwned>() -> Option<T> {
         }
     }
 }
+
+/// Send many events to the host in a single call.
+pub fn send_events<'a, T: Serialize + 'a>(events: impl IntoIterator<Item = &'a T>) {
+    let Some(batch) = encode_batch(events) else {
+        return;
+    };
+
+    unsafe {
+        crate::ffi::send_serialized_events(batch.as_ptr(), batch.len());
+    }
+}
+
+/// Get every pending event from the host in a single call.
+pub fn get_events<T: DeserializeOwned>() -> Vec<T> {
+    let buffer =
+        crate::ffi::read_from_host(|buffer, len| unsafe { crate::ffi::get_events(buffer, len) });
+
+    decode_batch(buffer)
+}
+
+/// Send many events to the host on an additional channel.
+///
+/// `channel` is the `TYPE_UUID` of the event type, as given by `as_u64_pair`.
+pub fn send_channel_events<'a, T: Serialize + 'a>(
+    channel: (u64, u64),
+    events: impl IntoIterator<Item = &'a T>,
+) {
+    let Some(batch) = encode_batch(events) else {
+        return;
+    };
+
+    unsafe {
+        crate::ffi::send_channel_events(channel.0, channel.1, batch.as_ptr(), batch.len());
+    }
+}
+
+/// Get every pending event from the host on an additional channel.
+///
+/// `channel` is the `TYPE_UUID` of the event type, as given by `as_u64_pair`.
+pub fn get_channel_events<T: DeserializeOwned>(channel: (u64, u64)) -> Vec<T> {
+    let buffer = crate::ffi::read_from_host(|buffer, len| unsafe {
+        crate::ffi::get_channel_events(channel.0, channel.1, buffer, len)
+    });
+
+    decode_batch(buffer)
+}
+
+/// Serialize events into a batch, or `None` if there's nothing to send
+fn encode_batch<'a, T: Serialize + 'a>(events: impl IntoIterator<Item = &'a T>) -> Option<Vec<u8>> {
+    let encoded: Vec<Vec<u8>> = events
+        .into_iter()
+        .filter_map(|event| match codec().encode(event) {
+            Ok(encoded) => Some(encoded),
+            Err(err) => {
+                error!("Failed to serialize event: {}", err);
+                None
+            }
+        })
+        .collect();
+
+    if encoded.is_empty() {
+        return None;
+    }
+
+    Some(batch::encode(encoded.iter().map(Vec::as_slice)))
+}
+
+fn decode_batch<T: DeserializeOwned>(buffer: Option<Vec<u8>>) -> Vec<T> {
+    let Some(buffer) = buffer else {
+        return Vec::new();
+    };
+
+    batch::decode(&buffer)
+        .filter_map(|event| match codec().decode(event) {
+            Ok(event) => Some(event),
+            Err(err) => {
+                error!("Failed to deserialize event from host: {}", err);
+                None
+            }
+        })
+        .collect()
+}
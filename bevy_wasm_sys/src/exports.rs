@@ -0,0 +1,34 @@
+//! Functions the game can call with `WasmInstance::call`. Export them with [`export`](crate::export).
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{codec::codec, error};
+
+/// Run an exported function with the arguments the game called it with, and hand its result back
+///
+/// Called by the code [`export`](crate::export) generates.
+#[doc(hidden)]
+pub fn handle_call<Args, Ret, F>(function: F)
+where
+    Args: DeserializeOwned,
+    Ret: Serialize,
+    F: FnOnce(Args) -> Ret,
+{
+    // Arguments such as `()` serialize to nothing
+    let args =
+        crate::ffi::read_from_host(|buffer, len| unsafe { crate::ffi::get_call_args(buffer, len) })
+            .unwrap_or_default();
+
+    let args = match codec().decode(&args) {
+        Ok(args) => args,
+        Err(err) => {
+            error!("Failed to deserialize call arguments from host: {}", err);
+            return;
+        }
+    };
+
+    match codec().encode(&function(args)) {
+        Ok(result) => unsafe { crate::ffi::store_call_result(result.as_ptr(), result.len()) },
+        Err(err) => error!("Failed to serialize call result: {}", err),
+    }
+}
@@ -7,6 +7,8 @@ use std::ffi::c_void;
 #[cfg(feature = "bevy")]
 use bevy_app::App;
 
+pub use bevy_wasm_shared::version::FFI_VERSION;
+
 #[link(wasm_import_module = "host")]
 extern "C" {
     pub fn store_app(app: *const c_void);
@@ -14,11 +16,65 @@ extern "C" {
     pub fn console_warn(msg: *const u8, len: usize);
     pub fn console_error(msg: *const u8, len: usize);
     pub fn send_serialized_event(event: *const u8, len: usize);
+    /// Returns the length of the next event. Only copied, and removed from the queue, if it fits.
     pub fn get_next_event(event: *const u8, len: usize) -> usize;
+    /// Hand many events to the host at once, packed with [`bevy_wasm_shared::batch::encode`]
+    pub fn send_serialized_events(events: *const u8, len: usize);
+    /// Returns the length of all pending events, packed with [`bevy_wasm_shared::batch::encode`].
+    /// Only copied, and removed from the queue, if they fit.
+    pub fn get_events(events: *const u8, len: usize) -> usize;
+    /// Like [`send_serialized_events`], on the channel identified by the event type's `TypeUuid`
+    pub fn send_channel_events(uuid_0: u64, uuid_1: u64, events: *const u8, len: usize);
+    /// Like [`get_events`], on the channel identified by the event type's `TypeUuid`
+    pub fn get_channel_events(uuid_0: u64, uuid_1: u64, events: *const u8, len: usize) -> usize;
+    /// Run the host function called `name`. Returns a [`bevy_wasm_shared::host_function::HostCallStatus`].
+    pub fn call_host_function(
+        name: *const u8,
+        name_len: usize,
+        args: *const u8,
+        args_len: usize,
+    ) -> u32;
+    /// Returns the length of the last host function's result. Only copied, and forgotten, if it fits.
+    pub fn get_host_function_result(buffer: *const u8, len: usize) -> usize;
+    /// Returns the length of the arguments of the export the host is calling. Only copied, and forgotten, if they fit.
+    pub fn get_call_args(buffer: *const u8, len: usize) -> usize;
+    /// Hand the result of the export the host is calling back to it
+    pub fn store_call_result(result: *const u8, len: usize);
     /// Nanoseconds since the mod was loaded
     pub fn get_time_since_startup() -> u64;
     pub fn get_protocol_version() -> u64;
+    /// Returns the length of the resource's new value, a serialized `ResourceUpdate`.
+    /// Only copied, and marked as read, if it fits.
     pub fn get_resource(uuid_0: u64, uuid_1: u64, buffer: *const u8, buffer_len: usize) -> usize;
+    /// Ask for the whole value of a resource on the next update, after a patch that didn't apply
+    pub fn request_resource_resync(uuid_0: u64, uuid_1: u64);
+    /// Hand a new value of a resource shared with `share_resource_mut` to the host
+    pub fn store_resource(uuid_0: u64, uuid_1: u64, value: *const u8, len: usize);
+    /// Hand the mod's serialized state to the host during a hot reload
+    pub fn store_state(state: *const u8, len: usize);
+    /// Returns the length of the state saved by the previous instance. Only copied if it fits.
+    pub fn get_saved_state(buffer: *const u8, len: usize) -> usize;
+}
+
+/// Read a payload with one of the host functions above that report its length,
+/// trying again with a larger buffer if it didn't fit.
+///
+/// Returns `None` if the host had nothing to give.
+pub(crate) fn read_from_host(mut read: impl FnMut(*const u8, usize) -> usize) -> Option<Vec<u8>> {
+    let mut buffer = vec![0; 1024];
+    let mut len = read(buffer.as_mut_ptr(), buffer.len());
+
+    if len > buffer.len() {
+        buffer.resize(len, 0);
+        len = read(buffer.as_mut_ptr(), buffer.len());
+    }
+
+    if len == 0 || len > buffer.len() {
+        return None;
+    }
+
+    buffer.truncate(len);
+    Some(buffer)
 }
 
 /// This function is called by the host every frame.
@@ -2,20 +2,28 @@
 
 use std::ffi::c_void;
 
-use bevy_app::{App, Plugin};
+use bevy_app::{App, CoreSet, Plugin};
 use bevy_ecs::{
-    prelude::{EventReader, EventWriter},
-    system::ResMut,
+    prelude::{EventReader, EventWriter, IntoSystemConfig},
+    system::{ResMut, SystemChangeTick},
 };
-use bevy_wasm_shared::prelude::*;
+use bevy_reflect::TypeUuid;
+use bevy_wasm_shared::{prelude::*, version::default_version_checker};
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{
-    ecs::extern_res::ExternResources,
+    codec::set_codec,
+    ecs::{
+        extern_res::ExternResources,
+        persistent::{
+            apply_saved_state, PersistentResource, PersistentResourceEntry, PersistentResources,
+        },
+    },
     error,
-    events::{get_next_event, send_event},
+    events::{get_channel_events, get_events, send_channel_events, send_events},
     ffi::store_app,
     info,
+    rpc::RpcClient,
     time::Time,
 };
 
@@ -33,6 +41,9 @@ impl<T> Message for T where T: Send + Sync + Serialize + DeserializeOwned + 'sta
 pub struct FFIPlugin<In: Message, Out: Message> {
     protocol_version: Version,
     protocol_version_checker: Box<dyn Fn(Version, Version) -> bool + Send + Sync + 'static>,
+    persistent_resources: Vec<PersistentResourceEntry>,
+    channels: Vec<fn(&mut App)>,
+    codec: Codec,
     _in: std::marker::PhantomData<In>,
     _out: std::marker::PhantomData<Out>,
 }
@@ -46,12 +57,10 @@ impl<In: Message, Out: Message> FFIPlugin<In, Out> {
         );
         Self {
             protocol_version,
-            protocol_version_checker: Box::new(|host_version, mod_version| {
-                // Check that the names match
-                host_version.name_hash == mod_version.name_hash
-                // Check that the major versions match
-                && host_version.major == mod_version.major
-            }),
+            protocol_version_checker: Box::new(default_version_checker),
+            persistent_resources: Vec::new(),
+            channels: Vec::new(),
+            codec: Codec::default(),
             _in: std::marker::PhantomData,
             _out: std::marker::PhantomData,
         }
@@ -59,6 +68,7 @@ impl<In: Message, Out: Message> FFIPlugin<In, Out> {
 
     /// Set a custom protocol version checker to ensure your mod is compatible with the game
     ///
+    /// The checker is called with the mod's version first and the game's version second.
     /// The default version checker only ensures the major versions match.
     ///
     /// i.e. `1.0.0` is compatible with `1.1.0` but not `2.0.0`
@@ -71,10 +81,49 @@ impl<In: Message, Out: Message> FFIPlugin<In, Out> {
             ..self
         }
     }
+
+    /// Serialize messages and shared resources with `codec` instead of bincode
+    ///
+    /// Must be the codec the game set with `WasmPlugin::with_codec`.
+    pub fn with_codec(self, codec: Codec) -> Self {
+        Self { codec, ..self }
+    }
+
+    /// Add a channel with its own message types, next to the plugin's `In` and `Out` messages
+    ///
+    /// Must match a channel the game added with `WasmPlugin::add_channel`.
+    pub fn add_channel<ChannelIn, ChannelOut>(mut self) -> Self
+    where
+        ChannelIn: Message + TypeUuid,
+        ChannelOut: Message + TypeUuid,
+    {
+        self.channels.push(add_channel::<ChannelIn, ChannelOut>);
+        self
+    }
+
+    /// Send requests of type `Req` to the game with `ResMut<RpcClient<Req>>`
+    ///
+    /// Must match a request the game added with `WasmPlugin::add_rpc`.
+    pub fn add_rpc<Req: Request + TypeUuid>(mut self) -> Self {
+        self.channels.push(add_rpc::<Req>);
+        self
+    }
+
+    /// Keep a resource when the mod is hot reloaded
+    ///
+    /// The new instance gets the value the resource had in the old one,
+    /// replacing whatever its startup systems inserted.
+    pub fn persist_resource<T: PersistentResource>(mut self) -> Self {
+        self.persistent_resources
+            .push(PersistentResourceEntry::new::<T>());
+        self
+    }
 }
 
 impl<In: Message, Out: Message> Plugin for FFIPlugin<In, Out> {
     fn build(&self, app: &mut App) {
+        set_codec(self.codec);
+
         let host_version = unsafe { crate::ffi::get_protocol_version() };
         let host_version = Version::from_u64(host_version);
         if !(*self.protocol_version_checker)(self.protocol_version, host_version) {
@@ -94,10 +143,16 @@ impl<In: Message, Out: Message> Plugin for FFIPlugin<In, Out> {
             .add_event::<Out>()
             .insert_resource(Time::new())
             .insert_resource(ExternResources::new())
+            .insert_resource(PersistentResources::new(self.persistent_resources.clone()))
+            .add_system(apply_saved_state.in_base_set(CoreSet::First))
             .add_system(update_time)
-            .add_system(fetch_resources)
+            .add_system(fetch_resources.in_base_set(CoreSet::PreUpdate))
             .add_system(event_listener::<In>)
-            .add_system(event_sender::<Out>);
+            .add_system(event_sender::<Out>)
+            .add_system(store_resources.in_base_set(CoreSet::Last));
+        for add_channel in self.channels.iter() {
+            add_channel(app);
+        }
         // .add_system_to_stage(CoreStage::First, update_time.at_start())
         // .add_system_to_stage(CoreStage::PreUpdate, fetch_resources)
         // .add_system_to_stage(CoreStage::PreUpdate, event_listener::<In>)
@@ -105,20 +160,44 @@ impl<In: Message, Out: Message> Plugin for FFIPlugin<In, Out> {
     }
 }
 
-fn fetch_resources(mut resources: ResMut<ExternResources>) {
-    resources.fetch_all();
+fn fetch_resources(mut resources: ResMut<ExternResources>, ticks: SystemChangeTick) {
+    resources.fetch_all(ticks.change_tick());
+}
+
+fn store_resources(mut resources: ResMut<ExternResources>) {
+    resources.store_all();
 }
 
 fn event_listener<M: Message>(mut events: EventWriter<M>) {
-    while let Some(event) = get_next_event() {
-        events.send(event);
-    }
+    events.send_batch(get_events());
 }
 
 fn event_sender<M: Message>(mut events: EventReader<M>) {
-    for event in events.iter() {
-        send_event(event);
-    }
+    send_events(events.iter());
+}
+
+fn add_channel<ChannelIn: Message + TypeUuid, ChannelOut: Message + TypeUuid>(app: &mut App) {
+    app.add_event::<ChannelIn>()
+        .add_event::<ChannelOut>()
+        .add_system(channel_listener::<ChannelIn>)
+        .add_system(channel_sender::<ChannelOut>);
+}
+
+fn channel_listener<M: Message + TypeUuid>(mut events: EventWriter<M>) {
+    events.send_batch(get_channel_events(M::TYPE_UUID.as_u64_pair()));
+}
+
+fn channel_sender<M: Message + TypeUuid>(mut events: EventReader<M>) {
+    send_channel_events(M::TYPE_UUID.as_u64_pair(), events.iter());
+}
+
+fn add_rpc<Req: Request + TypeUuid>(app: &mut App) {
+    app.insert_resource(RpcClient::<Req>::new(Req::TYPE_UUID.as_u64_pair()))
+        .add_system(poll_rpc_client::<Req>.in_base_set(CoreSet::First));
+}
+
+fn poll_rpc_client<Req: Request>(mut client: ResMut<RpcClient<Req>>) {
+    client.poll();
 }
 
 fn app_runner(app: App) {
@@ -0,0 +1,73 @@
+//! Call functions the game added with `WasmPlugin::add_host_function`. See [`call_host_function`],
+//! or declare them with [`host_function!`](crate::host_function).
+
+use std::fmt;
+
+use bevy_wasm_shared::{codec::CodecError, host_function::HostCallStatus};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::codec::codec;
+
+/// Why a host function call failed
+#[derive(Debug)]
+pub enum HostCallError {
+    /// The game has no function with that name
+    UnknownFunction,
+
+    /// The game couldn't read the arguments or write the result. Check that both sides agree on the types.
+    Failed,
+
+    /// The arguments or the result could not be serialized on the mod's side
+    Codec(CodecError),
+}
+
+impl fmt::Display for HostCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostCallError::UnknownFunction => write!(f, "the game has no such host function"),
+            HostCallError::Failed => write!(f, "the game failed to run the host function"),
+            HostCallError::Codec(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for HostCallError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HostCallError::Codec(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<CodecError> for HostCallError {
+    fn from(err: CodecError) -> Self {
+        HostCallError::Codec(err)
+    }
+}
+
+/// Call the host function registered as `name` and wait for its result
+pub fn call_host_function<Args, Ret>(name: &str, args: &Args) -> Result<Ret, HostCallError>
+where
+    Args: Serialize + ?Sized,
+    Ret: DeserializeOwned,
+{
+    let args = codec().encode(args)?;
+
+    let status = unsafe {
+        crate::ffi::call_host_function(name.as_ptr(), name.len(), args.as_ptr(), args.len())
+    };
+    match HostCallStatus::from_u32(status) {
+        HostCallStatus::Ok => {}
+        HostCallStatus::UnknownFunction => return Err(HostCallError::UnknownFunction),
+        HostCallStatus::Failed => return Err(HostCallError::Failed),
+    }
+
+    // Results such as `()` serialize to nothing
+    let result = crate::ffi::read_from_host(|buffer, len| unsafe {
+        crate::ffi::get_host_function_result(buffer, len)
+    })
+    .unwrap_or_default();
+
+    Ok(codec().decode(&result)?)
+}
@@ -8,9 +8,15 @@ To make a game that can use WebAssembly mods, see the sister crate [bevy_wasm](h
 
 #![deny(missing_docs)]
 
+pub use bevy_wasm_sys_macros::export;
+
+pub mod codec;
 pub mod events;
+pub mod exports;
 pub mod ffi;
+pub mod host_functions;
 pub mod macros;
+pub mod rpc;
 
 #[cfg(feature = "bevy")]
 pub mod ecs;
@@ -23,8 +29,13 @@ pub mod time;
 
 /// Convenience re-exports
 pub mod prelude {
-    pub use crate::macros::*;
-    pub use crate::{error, info, warn};
+    pub use crate::{
+        error, export, export_protocol_version, host_function,
+        host_functions::HostCallError,
+        info,
+        rpc::{RpcClient, RpcError, RpcHandle},
+        warn,
+    };
     pub use bevy_wasm_shared::prelude::*;
 
     #[cfg(feature = "bevy")]
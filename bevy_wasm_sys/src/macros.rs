@@ -1,4 +1,4 @@
-//! Macros used for printing to the screen
+//! Macros used for printing to the screen and talking to the host
 
 /// Calls `bevy_log::info!` macro on the host
 #[macro_export]
@@ -29,3 +29,57 @@ macro_rules! error {
         unsafe { $crate::ffi::console_error(string.as_ptr(), string.len()) };
     }};
 }
+
+/// Export the mod's protocol version, and the FFI version of this crate, so the host can check
+/// them before building the mod. Also exports the mod's [`codec`](crate::codec::codec), which
+/// the host checks once the mod built its app.
+///
+/// Call this once at the root of your mod's crate:
+///
+/// ```ignore
+/// bevy_wasm_sys::export_protocol_version!(PROTOCOL_VERSION);
+/// ```
+#[macro_export]
+macro_rules! export_protocol_version {
+    ($version:expr) => {
+        #[no_mangle]
+        pub extern "C" fn get_mod_protocol_version() -> u64 {
+            $version.to_u64()
+        }
+
+        #[no_mangle]
+        pub extern "C" fn get_mod_ffi_version() -> u32 {
+            $crate::ffi::FFI_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn get_mod_codec() -> u32 {
+            $crate::codec::codec().to_u8() as u32
+        }
+    };
+}
+
+/// Declare functions the game added with `WasmPlugin::add_host_function`
+///
+/// Each declaration becomes a function that takes its argument by reference and returns a
+/// `Result` with [`HostCallError`](crate::host_functions::HostCallError). The function's name
+/// is the one the game registered it under.
+///
+/// ```ignore
+/// bevy_wasm_sys::host_function! {
+///     pub fn find_path(PathQuery) -> Option<Path>;
+/// }
+/// ```
+#[macro_export]
+macro_rules! host_function {
+    ($($(#[$attr:meta])* $vis:vis fn $name:ident($args:ty) -> $ret:ty;)*) => {
+        $(
+            $(#[$attr])*
+            $vis fn $name(
+                args: &$args,
+            ) -> ::std::result::Result<$ret, $crate::host_functions::HostCallError> {
+                $crate::host_functions::call_host_function(stringify!($name), args)
+            }
+        )*
+    };
+}
@@ -0,0 +1,126 @@
+//! Send requests to the game and get its response. See [`RpcClient`].
+
+use std::{
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy_wasm_shared::rpc::Request;
+
+use crate::events::{get_channel_events, send_channel_events};
+
+/// How long a call waits for the game's response before failing with [`RpcError::TimedOut`]
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Why a call didn't get a response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RpcError {
+    /// The game didn't respond in time
+    TimedOut,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::TimedOut => write!(f, "the game didn't respond in time"),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+type CallResult<Res> = Arc<Mutex<Option<Result<Res, RpcError>>>>;
+
+/// The response to a call made with [`RpcClient::call`], once it arrives
+pub struct RpcHandle<Res> {
+    result: CallResult<Res>,
+}
+
+impl<Res> RpcHandle<Res> {
+    /// Take the response, or the reason there won't be one. `None` while the call is still pending.
+    pub fn try_take(&self) -> Option<Result<Res, RpcError>> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+struct PendingCall<Res> {
+    /// Time since startup after which the call times out
+    deadline: Duration,
+    result: CallResult<Res>,
+}
+
+/// Sends requests of type `Req` to the game and matches them with their responses
+///
+/// With Bevy, register the request with `FFIPlugin::add_rpc` and use `ResMut<RpcClient<Req>>`.
+/// Without Bevy, create the client yourself and call [`RpcClient::poll`] every update.
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct RpcClient<Req: Request> {
+    channel: (u64, u64),
+    timeout: Duration,
+    next_id: u64,
+    pending: HashMap<u64, PendingCall<Req::Response>>,
+    _req: PhantomData<Req>,
+}
+
+impl<Req: Request> RpcClient<Req> {
+    /// Create a client for the request's channel: its `TYPE_UUID`, as given by `as_u64_pair`
+    pub fn new(channel: (u64, u64)) -> Self {
+        Self {
+            channel,
+            timeout: DEFAULT_TIMEOUT,
+            next_id: 0,
+            pending: HashMap::new(),
+            _req: PhantomData,
+        }
+    }
+
+    /// Set how long calls made from now on wait for a response. Defaults to [`DEFAULT_TIMEOUT`].
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Send a request to the game
+    pub fn call(&mut self, request: &Req) -> RpcHandle<Req::Response> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        send_channel_events(self.channel, [&(id, request)]);
+
+        let result = CallResult::default();
+        self.pending.insert(
+            id,
+            PendingCall {
+                deadline: time_since_startup() + self.timeout,
+                result: result.clone(),
+            },
+        );
+
+        RpcHandle { result }
+    }
+
+    /// Hand the responses that arrived to their calls, and time out the calls that waited too long
+    pub fn poll(&mut self) {
+        for (id, response) in get_channel_events::<(u64, Req::Response)>(self.channel) {
+            // Responses to calls that already timed out are dropped
+            if let Some(call) = self.pending.remove(&id) {
+                *call.result.lock().unwrap() = Some(Ok(response));
+            }
+        }
+
+        let now = time_since_startup();
+        self.pending.retain(|_, call| {
+            if now < call.deadline {
+                return true;
+            }
+            *call.result.lock().unwrap() = Some(Err(RpcError::TimedOut));
+            false
+        });
+    }
+}
+
+fn time_since_startup() -> Duration {
+    Duration::from_nanos(unsafe { crate::ffi::get_time_since_startup() })
+}
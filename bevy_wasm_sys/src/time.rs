@@ -87,9 +87,13 @@ impl Time {
     /// # Examples
     ///
     /// ```
-    /// # use bevy_time::prelude::*;
-    /// # use bevy_ecs::prelude::*;
-    /// # use bevy_utils::Duration;
+    /// # use bevy_wasm_sys::prelude::*;
+    /// # use std::time::Duration;
+    /// # // Outside of a mod, nothing provides the host's clock
+    /// # #[no_mangle]
+    /// # extern "C" fn get_time_since_startup() -> u64 {
+    /// #     0
+    /// # }
     /// # fn main () {
     /// #     test_health_system();
     /// # }
@@ -109,20 +113,19 @@ impl Time {
     /// fn test_health_system() {
     ///     let mut world = World::default();
     ///     let mut time = Time::default();
-    ///     time.update();
+    ///     time.update_with_instant(Duration::ZERO);
     ///     world.insert_resource(time);
     ///     world.insert_resource(Health { health_value: 0.2 });
     ///
-    ///     let mut update_stage = SystemStage::parallel();
-    ///     update_stage.add_system(health_system);
+    ///     let mut schedule = Schedule::new();
+    ///     schedule.add_system(health_system);
     ///
     ///     // Simulate that 30 ms have passed
     ///     let mut time = world.resource_mut::<Time>();
-    ///     let last_update = time.last_update().unwrap(); // Don't unwrap in a mod
-    ///     time.update_with_instant(last_update + Duration::from_millis(30));
+    ///     time.update_with_instant(Duration::from_millis(30));
     ///
     ///     // Run system
-    ///     update_stage.run(&mut world);
+    ///     schedule.run(&mut world);
     ///
     ///     // Check that 0.003 has been added to the health value
     ///     let expected_health_value = 0.2 + 0.1 * 0.03;
@@ -0,0 +1,126 @@
+/*!
+Procedural macros for mods. Use them through [bevy_wasm_sys](https://docs.rs/bevy_wasm_sys).
+*/
+
+#![deny(missing_docs)]
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote, ToTokens};
+use syn::{parse_macro_input, spanned::Spanned, FnArg, ItemFn};
+
+/// Export a function so the game can call it with `WasmInstance::call`
+///
+/// The function is exported under its own name. A single argument is passed as is,
+/// several arguments as a tuple. Arguments and the result are serialized with the mod's codec.
+///
+/// Names the game looks up itself, like `update`, `build_app` or anything starting with `get_mod_`,
+/// are reserved and can't be exported.
+///
+/// ```ignore
+/// #[bevy_wasm_sys::export]
+/// fn on_damage(damage: Damage) -> Reaction {
+///     Reaction::Flinch
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn export(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return error(Span::call_site(), "`export` doesn't take arguments");
+    }
+
+    let function = parse_macro_input!(item as ItemFn);
+    let sig = &function.sig;
+    if !sig.generics.params.is_empty() {
+        return error(sig.generics.span(), "exported functions can't be generic");
+    }
+    if let Some(asyncness) = sig.asyncness {
+        return error(asyncness.span(), "exported functions can't be async");
+    }
+
+    let mut arg_names = Vec::new();
+    let mut arg_types = Vec::new();
+    for (i, input) in sig.inputs.iter().enumerate() {
+        match input {
+            FnArg::Receiver(receiver) => {
+                return error(
+                    receiver.self_token.span,
+                    "exported functions can't take `self`",
+                );
+            }
+            FnArg::Typed(arg) => {
+                arg_names.push(format_ident!("arg_{}", i));
+                arg_types.push(arg.ty.to_token_stream());
+            }
+        }
+    }
+
+    let (args_pat, args_type) = match (arg_names.as_slice(), arg_types.as_slice()) {
+        ([name], [ty]) => (quote!(#name), quote!(#ty)),
+        _ => (quote!((#(#arg_names,)*)), quote!((#(#arg_types,)*))),
+    };
+
+    let name = &sig.ident;
+    let export_name = name.to_string();
+    if is_reserved(&export_name) {
+        return error(
+            name.span(),
+            &format!(
+                "`{}` is reserved by bevy_wasm, export another name",
+                export_name
+            ),
+        );
+    }
+    let wrapper = format_ident!("__bevy_wasm_export_{}", name);
+
+    quote! {
+        #function
+
+        #[doc(hidden)]
+        #[export_name = #export_name]
+        pub extern "C" fn #wrapper() {
+            ::bevy_wasm_sys::exports::handle_call(|#args_pat: #args_type| #name(#(#arg_names),*));
+        }
+    }
+    .into()
+}
+
+/// Exports the game calls or looks up itself, which a mod's own export would clash with
+const RESERVED_NAMES: &[&str] = &[
+    "build_app",
+    "update",
+    "save_state",
+    "restore_state",
+    "memory",
+];
+
+fn is_reserved(name: &str) -> bool {
+    RESERVED_NAMES.contains(&name) || name.starts_with("get_mod_")
+}
+
+fn error(span: Span, message: &str) -> TokenStream {
+    syn::Error::new(span, message).to_compile_error().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_the_game_uses_are_reserved() {
+        for name in [
+            "build_app",
+            "update",
+            "save_state",
+            "restore_state",
+            "memory",
+            "get_mod_protocol_version",
+            "get_mod_anything",
+        ] {
+            assert!(is_reserved(name), "{} should be reserved", name);
+        }
+        for name in ["on_damage", "updated", "get_modifier", "build"] {
+            assert!(!is_reserved(name), "{} should not be reserved", name);
+        }
+    }
+}
@@ -1,24 +1,23 @@
 use bevy::prelude::*;
 use bevy_wasm::prelude::*;
-use cubes_protocol::{HostMessage, ModMessage, PROTOCOL_VERSION};
+use cubes_protocol::{HostMessage, ModMessage, SpawnCube, SpawnedCube, PROTOCOL_VERSION};
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_plugin(WasmPlugin::<HostMessage, ModMessage>::new(PROTOCOL_VERSION))
+        .add_plugin(
+            WasmPlugin::<HostMessage, ModMessage>::new(PROTOCOL_VERSION).add_rpc::<SpawnCube>(),
+        )
         .add_startup_system(insert_mods)
         .add_startup_system(setup)
+        .add_system(spawn_cubes_for_mods)
         .add_system(update_cubes_from_mods)
         .run();
 }
 
 fn insert_mods(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn(WasmMod {
-        wasm: asset_server.load("mod_with_bevy.wasm"),
-    });
-    commands.spawn(WasmMod {
-        wasm: asset_server.load("mod_without_bevy.wasm"),
-    });
+    commands.spawn(WasmMod::new(asset_server.load("mod_with_bevy.wasm")));
+    commands.spawn(WasmMod::new(asset_server.load("mod_without_bevy.wasm")));
 }
 
 /// set up a simple 3D scene
@@ -53,12 +52,30 @@ fn setup(
     });
 }
 
-fn update_cubes_from_mods(
+fn spawn_cubes_for_mods(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut requests: EventReader<ModRequest<SpawnCube>>,
+) {
+    for request in requests.iter() {
+        info!("Spawning cube from mod {:?}!", request.source);
+        let color = request.request.color;
+        let entity_id = commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Cube { size: 0.5 })),
+                material: materials.add(Color::rgb(color.0, color.1, color.2).into()),
+                transform: Transform::from_xyz(0.0, 0.5, 0.0),
+                ..default()
+            })
+            .id()
+            .index();
+        request.respond(SpawnedCube { entity_id });
+    }
+}
+
+fn update_cubes_from_mods(
     mut mod_messages: EventReader<ModMessage>,
-    mut host_messages: EventWriter<HostMessage>,
     mut query: Query<&mut Transform>,
 ) {
     for event in mod_messages.iter() {
@@ -68,22 +85,6 @@ fn update_cubes_from_mods(
                     transform.translation = Vec3::new(*x, *y, *z);
                 }
             }
-            ModMessage::SpawnCube { mod_state, color } => {
-                info!("Spawning cube from mod {:x}!", mod_state);
-                let entity_id = commands
-                    .spawn(PbrBundle {
-                        mesh: meshes.add(Mesh::from(shape::Cube { size: 0.5 })),
-                        material: materials.add(Color::rgb(color.0, color.1, color.2).into()),
-                        transform: Transform::from_xyz(0.0, 0.5, 0.0),
-                        ..default()
-                    })
-                    .id()
-                    .index();
-                host_messages.send(HostMessage::SpawnedCube {
-                    mod_state: *mod_state,
-                    entity_id,
-                });
-            }
         }
     }
 }
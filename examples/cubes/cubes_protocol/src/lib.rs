@@ -1,3 +1,4 @@
+use bevy_reflect::TypeUuid;
 use bevy_wasm_shared::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -6,24 +7,11 @@ pub const PROTOCOL_VERSION: Version = version!();
 
 /// Messages passed `Host -> Mod`
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum HostMessage {
-    /// A cube was spawned. This is its entity id.
-    SpawnedCube {
-        /// mod-specific state, specified on [`ModMessage::SpawnCube`]
-        mod_state: u64,
-        entity_id: u32,
-    },
-}
+pub enum HostMessage {}
 
 /// Messages passed `Mod -> Host`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ModMessage {
-    /// Spawn a cube
-    SpawnCube {
-        /// This is a mod-specific state that will be passed back to the mod when the cube is spawned
-        mod_state: u64,
-        color: (f32, f32, f32),
-    },
     /// Move a cube given an entity id
     MoveCube {
         entity_id: u32,
@@ -32,3 +20,20 @@ pub enum ModMessage {
         z: f32,
     },
 }
+
+/// Ask the game to spawn a cube. It answers with a [`SpawnedCube`].
+#[derive(Debug, Clone, Serialize, Deserialize, TypeUuid)]
+#[uuid = "3beee4b3-f835-4eba-91cf-da6ff690f42e"]
+pub struct SpawnCube {
+    pub color: (f32, f32, f32),
+}
+
+impl Request for SpawnCube {
+    type Response = SpawnedCube;
+}
+
+/// The cube was spawned. This is its entity id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnedCube {
+    pub entity_id: u32,
+}
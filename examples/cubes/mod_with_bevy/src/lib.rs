@@ -1,21 +1,26 @@
 use bevy_wasm_sys::prelude::*;
-use cubes_protocol::{HostMessage, ModMessage, PROTOCOL_VERSION};
+use cubes_protocol::{HostMessage, ModMessage, SpawnCube, SpawnedCube, PROTOCOL_VERSION};
+use serde::{Deserialize, Serialize};
 
-const MOD_STATE: u64 = 0xa6e79eb9; // Should be unique to each mod
+export_protocol_version!(PROTOCOL_VERSION);
 
 #[no_mangle]
 #[allow(clippy::missing_safety_doc)]
 pub unsafe extern "C" fn build_app() {
     info!("Hello from build_app inside mod_with_bevy!");
     App::new()
-        .add_plugin(FFIPlugin::<HostMessage, ModMessage>::new(PROTOCOL_VERSION))
+        .add_plugin(
+            FFIPlugin::<HostMessage, ModMessage>::new(PROTOCOL_VERSION)
+                // Keep moving the same cube when the mod is hot reloaded
+                .persist_resource::<CubePosition>()
+                .add_rpc::<SpawnCube>(),
+        )
         .add_startup_system(startup_system)
         .add_system(update_cube)
-        .add_system(listen_for_message)
         .run();
 }
 
-#[derive(Resource)]
+#[derive(Resource, Serialize, Deserialize)]
 struct CubePosition {
     entity_id: Option<u32>,
     x: f32,
@@ -23,7 +28,7 @@ struct CubePosition {
     z: f32,
 }
 
-fn startup_system(mut commands: Commands, mut events: EventWriter<ModMessage>) {
+fn startup_system(mut commands: Commands) {
     info!("Hello from startup_system inside mod!");
     warn!("This is a warning!");
     error!("This is an error!");
@@ -33,16 +38,14 @@ fn startup_system(mut commands: Commands, mut events: EventWriter<ModMessage>) {
         y: 0.0,
         z: 0.0,
     });
-    events.send(ModMessage::SpawnCube {
-        mod_state: MOD_STATE,
-        color: (0.0, 1.0, 0.0),
-    });
 }
 
 fn update_cube(
     mut resource: ResMut<CubePosition>,
     time: Res<Time>,
     mut events: EventWriter<ModMessage>,
+    mut spawn_cube: ResMut<RpcClient<SpawnCube>>,
+    mut spawning: Local<Option<RpcHandle<SpawnedCube>>>,
 ) {
     let time: f32 = time.elapsed_seconds();
     // Move the cube in a circle
@@ -52,7 +55,20 @@ fn update_cube(
     // Ensure the cube has been spawned on the host
     let entity_id = match resource.entity_id {
         Some(entity_id) => entity_id,
-        None => return,
+        None => {
+            let handle = spawning.get_or_insert_with(|| {
+                spawn_cube.call(&SpawnCube {
+                    color: (0.0, 1.0, 0.0),
+                })
+            });
+            match handle.try_take() {
+                Some(Ok(SpawnedCube { entity_id })) => resource.entity_id = Some(entity_id),
+                Some(Err(err)) => error!("Failed to spawn cube: {}", err),
+                None => return,
+            }
+            *spawning = None;
+            return;
+        }
     };
 
     // Tell the game we moved the cube
@@ -63,15 +79,3 @@ fn update_cube(
         z: resource.z,
     });
 }
-
-fn listen_for_message(mut events: EventReader<HostMessage>, mut resource: ResMut<CubePosition>) {
-    for event in events.iter() {
-        if let HostMessage::SpawnedCube {
-            entity_id,
-            mod_state: MOD_STATE, // Must be for us
-        } = event
-        {
-            resource.entity_id = Some(*entity_id);
-        }
-    }
-}
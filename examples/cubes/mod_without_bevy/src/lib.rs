@@ -1,36 +1,42 @@
-use bevy_wasm_sys::events::get_next_event;
+use bevy_reflect::TypeUuid;
 use bevy_wasm_sys::events::send_event;
 use bevy_wasm_sys::ffi;
-use bevy_wasm_sys::info;
-use cubes_protocol::{HostMessage, ModMessage};
+use bevy_wasm_sys::rpc::{RpcClient, RpcHandle};
+use bevy_wasm_sys::{error, info};
+use cubes_protocol::{ModMessage, SpawnCube, SpawnedCube, PROTOCOL_VERSION};
 
 use std::ffi::c_void;
 use std::time::Duration;
 
-const MOD_STATE: u64 = 0xf6a11546; // Should be unique to each mod
+bevy_wasm_sys::export_protocol_version!(PROTOCOL_VERSION);
 
-#[derive(Debug)]
 struct AppState {
     entity_id: Option<u32>,
     x: f32,
     y: f32,
+    spawn_cube: RpcClient<SpawnCube>,
+    spawning: Option<RpcHandle<SpawnedCube>>,
 }
 
+const RED: SpawnCube = SpawnCube {
+    color: (1.0, 0.0, 0.0),
+};
+
 #[no_mangle]
 #[allow(clippy::missing_safety_doc)]
 pub unsafe extern "C" fn build_app() {
     info!("Hello from build_app inside mod_without_bevy!");
+    let mut spawn_cube = RpcClient::new(SpawnCube::TYPE_UUID.as_u64_pair());
+    let spawning = Some(spawn_cube.call(&RED));
+
     let app_state = AppState {
         entity_id: None,
         x: 0.0,
         y: 0.0,
+        spawn_cube,
+        spawning,
     };
 
-    send_event(&ModMessage::SpawnCube {
-        mod_state: MOD_STATE,
-        color: (1.0, 0.0, 0.0),
-    });
-
     let app = Box::new(app_state);
     let app_ptr = Box::into_raw(app);
     let app_ptr = app_ptr as *const c_void;
@@ -53,14 +59,18 @@ fn update_app_state(app_state: &mut AppState) {
     app_state.y = time.sin() + 1.5;
     app_state.x = -time.cos();
 
-    while let Some(event) = get_next_event::<HostMessage>() {
-        if let HostMessage::SpawnedCube {
-            entity_id,
-            mod_state: MOD_STATE, // Must be for us
-        } = event
-        {
-            app_state.entity_id = Some(entity_id);
-        }
+    app_state.spawn_cube.poll();
+    if let Some(result) = app_state.spawning.as_ref().and_then(RpcHandle::try_take) {
+        app_state.spawning = match result {
+            Ok(SpawnedCube { entity_id }) => {
+                app_state.entity_id = Some(entity_id);
+                None
+            }
+            Err(err) => {
+                error!("Failed to spawn cube, trying again: {}", err);
+                Some(app_state.spawn_cube.call(&RED))
+            }
+        };
     }
 
     let Some(entity_id) = app_state.entity_id else { return };
@@ -21,9 +21,7 @@ fn main() {
 }
 
 fn insert_mods(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn(WasmMod {
-        wasm: asset_server.load("shared_resources_mod.wasm"),
-    });
+    commands.spawn(WasmMod::new(asset_server.load("shared_resources_mod.wasm")));
 }
 
 fn update_resource(mut my_cool_resource: ResMut<MyCoolResource>) {
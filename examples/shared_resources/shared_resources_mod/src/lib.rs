@@ -1,6 +1,8 @@
 use bevy_wasm_sys::{ecs::extern_res::ExternResources, prelude::*};
 use shared_resources_protocol::{HostMessage, ModMessage, MyCoolResource, PROTOCOL_VERSION};
 
+export_protocol_version!(PROTOCOL_VERSION);
+
 #[no_mangle]
 #[allow(clippy::missing_safety_doc)]
 pub unsafe extern "C" fn build_app() {
@@ -20,5 +22,8 @@ fn startup_system(mut resources: ResMut<ExternResources>) {
 }
 
 fn print_resource_value(resource: ExternRes<MyCoolResource>) {
-    info!("{:?}", resource);
+    match resource.try_get() {
+        Ok(resource) => info!("{:?}", resource),
+        Err(err) => warn!("Can't read MyCoolResource: {}", err),
+    }
 }
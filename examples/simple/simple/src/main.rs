@@ -15,9 +15,7 @@ fn main() {
 }
 
 fn insert_mods(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn(WasmMod {
-        wasm: asset_server.load("simple_mod.wasm"),
-    });
+    commands.spawn(WasmMod::new(asset_server.load("simple_mod.wasm")));
 }
 
 fn listen_for_mod_messages(mut events: EventReader<ModMessage>) {
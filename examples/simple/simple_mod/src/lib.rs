@@ -1,6 +1,8 @@
 use bevy_wasm_sys::prelude::*;
 use simple_protocol::{GameMessage, ModMessage, PROTOCOL_VERSION};
 
+export_protocol_version!(PROTOCOL_VERSION);
+
 #[no_mangle]
 #[allow(clippy::missing_safety_doc)]
 pub unsafe extern "C" fn build_app() {